@@ -0,0 +1,332 @@
+use crossterm::style::{Attribute, Color, ContentStyle};
+use log::{trace, warn};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+use crate::{FSFile, FSFileType, LinkType};
+
+/// Colors used when LS_COLORS is not set, same as the GNU ls built in database
+static DEFAULT_LS_COLORS: &str = "rs=0:di=01;34:ln=01;36:mh=00:pi=40;33:so=01;35:do=01;35:\
+bd=40;33;01:cd=40;33;01:or=40;31;01:mi=00:su=37;41:sg=30;43:ca=00:tw=30;42:ow=34;42:\
+st=37;44:ex=01;32";
+
+/// File type keys that can be given in LS_COLORS
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Indicator {
+    Normal,
+    File,
+    Dir,
+    Link,
+    Fifo,
+    Socket,
+    Door,
+    BlockDevice,
+    CharDevice,
+    Orphan,
+    Missing,
+    Setuid,
+    Setgid,
+    Capability,
+    StickyOtherWritable,
+    OtherWritable,
+    Sticky,
+    Exec,
+    MultiHardlink,
+}
+
+impl Indicator {
+    fn from_key(key: &str) -> Option<Indicator> {
+        let indicator = match key {
+            "no" => Indicator::Normal,
+            "fi" => Indicator::File,
+            "di" => Indicator::Dir,
+            "ln" => Indicator::Link,
+            "pi" => Indicator::Fifo,
+            "so" => Indicator::Socket,
+            "do" => Indicator::Door,
+            "bd" => Indicator::BlockDevice,
+            "cd" => Indicator::CharDevice,
+            "or" => Indicator::Orphan,
+            "mi" => Indicator::Missing,
+            "su" => Indicator::Setuid,
+            "sg" => Indicator::Setgid,
+            "ca" => Indicator::Capability,
+            "tw" => Indicator::StickyOtherWritable,
+            "ow" => Indicator::OtherWritable,
+            "st" => Indicator::Sticky,
+            "ex" => Indicator::Exec,
+            "mh" => Indicator::MultiHardlink,
+            _ => return None,
+        };
+        Some(indicator)
+    }
+}
+
+/// Styles parsed from an LS_COLORS string
+pub struct LsColors {
+    indicators: HashMap<Indicator, ContentStyle>,
+    /// Glob suffixes such as `*.rs`, stored without the leading `*`
+    extensions: Vec<(String, ContentStyle)>,
+    /// `ln=target`, color links like the file they point to
+    link_as_target: bool,
+}
+
+impl LsColors {
+    /// Read LS_COLORS from the environment, falling back on the default colors
+    pub fn from_env() -> LsColors {
+        match std::env::var("LS_COLORS") {
+            Ok(ls_colors) if !ls_colors.is_empty() => LsColors::parse(&ls_colors),
+            _ => LsColors::parse(DEFAULT_LS_COLORS),
+        }
+    }
+
+    /// Parse a string of `key=sgr` pairs separated by `:`
+    pub fn parse(ls_colors: &str) -> LsColors {
+        let mut colors = LsColors {
+            indicators: HashMap::new(),
+            extensions: Vec::new(),
+            link_as_target: false,
+        };
+
+        for entry in ls_colors.split(':').filter(|e| !e.is_empty()) {
+            let Some((key, sgr)) = entry.split_once('=') else {
+                warn!("Invalid LS_COLORS entry: {}", entry);
+                continue;
+            };
+            if key == "ln" && sgr == "target" {
+                colors.link_as_target = true;
+                continue;
+            }
+            let style = parse_sgr(sgr);
+
+            if let Some(glob) = key.strip_prefix('*') {
+                colors.extensions.push((glob.to_string(), style));
+            } else if let Some(indicator) = Indicator::from_key(key) {
+                colors.indicators.insert(indicator, style);
+            } else {
+                trace!("Ignore unknown LS_COLORS key: {}", key);
+            }
+        }
+
+        colors
+    }
+
+    /// Find the style to print the name of the file with
+    pub fn style_for(&self, fs_file: &FSFile) -> Option<ContentStyle> {
+        let indicator = match &fs_file.entry_type {
+            FSFileType::Symlink(link) => match &link.target_metadata {
+                None => Indicator::Orphan,
+                Some(target_metadata) if self.link_as_target => indicator_of(target_metadata),
+                Some(_) => Indicator::Link,
+            },
+            FSFileType::Inaccessible(_) => Indicator::Missing,
+            _ => match &fs_file.metadata {
+                Some(metadata) => indicator_of(metadata),
+                None => Indicator::Missing,
+            },
+        };
+        self.style_for_indicator(indicator, &fs_file.name.to_string_lossy())
+    }
+
+    /// Find the style to print the target of a symbolic link with
+    pub fn style_for_target(&self, link: &LinkType) -> Option<ContentStyle> {
+        let indicator = match &link.target_metadata {
+            Some(target_metadata) => indicator_of(target_metadata),
+            None => Indicator::Missing,
+        };
+        self.style_for_indicator(indicator, &link.target.to_string_lossy())
+    }
+
+    fn style_for_indicator(&self, indicator: Indicator, name: &str) -> Option<ContentStyle> {
+        /* Like GNU ls, the extension is only matched for regular files */
+        if indicator == Indicator::File {
+            if let Some(style) = self.extension_style(name) {
+                return Some(style);
+            }
+        }
+
+        self.indicators
+            .get(&indicator)
+            .or_else(|| match indicator {
+                Indicator::File => self.indicators.get(&Indicator::Normal),
+                _ => None,
+            })
+            .copied()
+    }
+
+    fn extension_style(&self, name: &str) -> Option<ContentStyle> {
+        /* Later entries override earlier ones, so search from the back */
+        self.extensions
+            .iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, style)| *style)
+    }
+}
+
+/// Classify a file into the LS_COLORS key that applies to it
+fn indicator_of(metadata: &Metadata) -> Indicator {
+    let mode = metadata.mode();
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        let sticky = mode & 0o1000 != 0;
+        let other_writable = mode & 0o002 != 0;
+        match (sticky, other_writable) {
+            (true, true) => Indicator::StickyOtherWritable,
+            (false, true) => Indicator::OtherWritable,
+            (true, false) => Indicator::Sticky,
+            (false, false) => Indicator::Dir,
+        }
+    } else if file_type.is_file() {
+        if mode & 0o4000 != 0 {
+            Indicator::Setuid
+        } else if mode & 0o2000 != 0 {
+            Indicator::Setgid
+        } else if mode & 0o111 != 0 {
+            Indicator::Exec
+        } else {
+            Indicator::File
+        }
+    } else if file_type.is_symlink() {
+        Indicator::Link
+    } else if file_type.is_fifo() {
+        Indicator::Fifo
+    } else if file_type.is_socket() {
+        Indicator::Socket
+    } else if file_type.is_block_device() {
+        Indicator::BlockDevice
+    } else if file_type.is_char_device() {
+        Indicator::CharDevice
+    } else {
+        Indicator::Normal
+    }
+}
+
+/// Convert an SGR sequence like `01;38;5;208` to a crossterm style
+fn parse_sgr(sgr: &str) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    let codes: Vec<u8> = sgr.split(';').filter_map(|c| c.parse().ok()).collect();
+    let mut it = codes.into_iter();
+
+    while let Some(code) = it.next() {
+        match code {
+            0 => style = ContentStyle::new(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => style.attributes.set(Attribute::Underlined),
+            5 => style.attributes.set(Attribute::SlowBlink),
+            6 => style.attributes.set(Attribute::RapidBlink),
+            7 => style.attributes.set(Attribute::Reverse),
+            8 => style.attributes.set(Attribute::Hidden),
+            9 => style.attributes.set(Attribute::CrossedOut),
+            30..=37 => style.foreground_color = Some(ansi_color(code - 30)),
+            40..=47 => style.background_color = Some(ansi_color(code - 40)),
+            90..=97 => style.foreground_color = Some(ansi_color(code - 90 + 8)),
+            100..=107 => style.background_color = Some(ansi_color(code - 100 + 8)),
+            38 | 48 => {
+                let color = match it.next() {
+                    Some(5) => it.next().map(Color::AnsiValue),
+                    Some(2) => match (it.next(), it.next(), it.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb { r, g, b }),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if code == 38 {
+                    style.foreground_color = color;
+                } else {
+                    style.background_color = color;
+                }
+            }
+            39 => style.foreground_color = None,
+            49 => style.background_color = None,
+            _ => trace!("Ignore unsupported SGR code: {}", code),
+        }
+    }
+
+    style
+}
+
+/// Map the 16 standard terminal colors to crossterm colors
+fn ansi_color(n: u8) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::DarkRed,
+        2 => Color::DarkGreen,
+        3 => Color::DarkYellow,
+        4 => Color::DarkBlue,
+        5 => Color::DarkMagenta,
+        6 => Color::DarkCyan,
+        7 => Color::Grey,
+        8 => Color::DarkGrey,
+        9 => Color::Red,
+        10 => Color::Green,
+        11 => Color::Yellow,
+        12 => Color::Blue,
+        13 => Color::Magenta,
+        14 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_and_basic_colors() {
+        let style = parse_sgr("01;34");
+        assert!(style.attributes.has(Attribute::Bold));
+        assert_eq!(style.foreground_color, Some(Color::DarkBlue));
+        assert_eq!(style.background_color, None);
+
+        let style = parse_sgr("30;42");
+        assert_eq!(style.foreground_color, Some(Color::Black));
+        assert_eq!(style.background_color, Some(Color::DarkGreen));
+
+        let style = parse_sgr("91;104");
+        assert_eq!(style.foreground_color, Some(Color::Red));
+        assert_eq!(style.background_color, Some(Color::Blue));
+    }
+
+    #[test]
+    fn indexed_colors() {
+        let style = parse_sgr("38;5;208");
+        assert_eq!(style.foreground_color, Some(Color::AnsiValue(208)));
+        let style = parse_sgr("48;5;17;1");
+        assert_eq!(style.background_color, Some(Color::AnsiValue(17)));
+        assert!(style.attributes.has(Attribute::Bold));
+    }
+
+    #[test]
+    fn rgb_colors() {
+        let style = parse_sgr("38;2;255;128;0");
+        assert_eq!(
+            style.foreground_color,
+            Some(Color::Rgb {
+                r: 255,
+                g: 128,
+                b: 0
+            })
+        );
+        let style = parse_sgr("48;2;1;2;3");
+        assert_eq!(
+            style.background_color,
+            Some(Color::Rgb { r: 1, g: 2, b: 3 })
+        );
+        /* An incomplete color is ignored */
+        assert_eq!(parse_sgr("38;2;1;2").foreground_color, None);
+    }
+
+    #[test]
+    fn reset() {
+        assert_eq!(parse_sgr("0"), ContentStyle::new());
+        assert_eq!(parse_sgr("01;31;0"), ContentStyle::new());
+        let style = parse_sgr("01;31;0;32");
+        assert!(!style.attributes.has(Attribute::Bold));
+        assert_eq!(style.foreground_color, Some(Color::DarkGreen));
+        assert_eq!(parse_sgr("31;39").foreground_color, None);
+    }
+}
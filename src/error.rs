@@ -0,0 +1,82 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use thiserror::Error;
+
+/// Exit status of the listing, raised as problems are reported
+static EXIT_STATUS: AtomicI32 = AtomicI32::new(0);
+
+/// Exit status for problems like an unreadable subdirectory
+pub static MINOR: i32 = 1;
+/// Exit status for problems like an operand that cannot be accessed
+pub static SERIOUS: i32 = 2;
+
+#[derive(Error, Debug)]
+pub enum LsError {
+    #[error("cannot access '{}': {}", .path.display(), io_message(.source))]
+    Access { path: PathBuf, source: io::Error },
+
+    #[error("cannot open directory '{}': {}", .path.display(), io_message(.source))]
+    OpenDir { path: PathBuf, source: io::Error },
+
+    #[error("reading directory '{}': {}", .path.display(), io_message(.source))]
+    ReadDir { path: PathBuf, source: io::Error },
+
+    #[error("cannot read symbolic link '{}': {}", .path.display(), io_message(.source))]
+    ReadLink { path: PathBuf, source: io::Error },
+
+    #[error("cannot access '{}': unknown file type", .path.display())]
+    UnknownType { path: PathBuf },
+
+    #[error("{}: not listing already-listed directory", .path.display())]
+    AlreadyListed { path: PathBuf },
+
+    #[error("write error: {}", io_message(.0))]
+    Output(#[from] io::Error),
+
+    #[error("failed to read settings")]
+    Settings,
+}
+
+impl LsError {
+    /// Short description shown next to the entry in the listing
+    pub fn note(&self) -> String {
+        match self {
+            LsError::Access { source, .. } => format!("error accessing: {}", io_message(source)),
+            LsError::OpenDir { source, .. } => format!("error opening dir: {}", io_message(source)),
+            LsError::ReadDir { source, .. } => format!("error reading dir: {}", io_message(source)),
+            LsError::ReadLink { source, .. } => {
+                format!("error reading link: {}", io_message(source))
+            }
+            LsError::UnknownType { .. } => String::from("error: unknown file type"),
+            LsError::AlreadyListed { .. } => String::from("recursive, not followed"),
+            LsError::Output(source) => format!("error writing: {}", io_message(source)),
+            LsError::Settings => String::from("error reading settings"),
+        }
+    }
+
+    /// The output was closed, e.g. by `rls | head`, which is not worth reporting
+    pub fn is_broken_pipe(&self) -> bool {
+        matches!(self, LsError::Output(e) if e.kind() == io::ErrorKind::BrokenPipe)
+    }
+}
+
+/// Print the error on stderr and raise the exit status to at least `status`
+pub fn report(err: &LsError, status: i32) {
+    eprintln!("rls: {}", err);
+    EXIT_STATUS.fetch_max(status, Ordering::Relaxed);
+}
+
+/// Exit status like GNU ls: 0 when all went well, 1 for minor and 2 for serious problems
+pub fn exit_status() -> i32 {
+    EXIT_STATUS.load(Ordering::Relaxed)
+}
+
+/// Description of an I/O error without the `(os error N)` suffix, like ls prints it
+pub fn io_message(e: &io::Error) -> String {
+    let message = e.to_string();
+    match message.find(" (os error ") {
+        Some(i) => message[..i].to_string(),
+        None => message,
+    }
+}
@@ -0,0 +1,337 @@
+use crossterm::style::{Color, ContentStyle};
+use log::{trace, warn};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Status of the files in the repositories of the operands, read once with --git
+static STATUSES: OnceLock<Statuses> = OnceLock::new();
+
+/// Two letter status of a file as `git status --porcelain` prints it, the index
+/// first and the work tree second
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Status {
+    pub index: char,
+    pub worktree: char,
+}
+
+#[derive(Default)]
+struct Statuses {
+    /// Status of the files and the aggregated status of their directories, by the
+    /// path the listing shows them at
+    files: HashMap<PathBuf, Status>,
+    /// Directories git reports as a whole, like ignored ones, that give their
+    /// status to everything below them
+    dirs: HashMap<PathBuf, Status>,
+}
+
+impl Status {
+    fn untracked(&self) -> bool {
+        self.index == '?'
+    }
+
+    fn ignored(&self) -> bool {
+        self.index == '!'
+    }
+
+    /// Combine the status of two files below the same directory, where changes to
+    /// tracked files take precedence over untracked files
+    fn merge(self, other: Status) -> Status {
+        let changed = |s: &Status| !s.untracked() && !s.ignored();
+        match (changed(&self), changed(&other)) {
+            (true, true) => Status {
+                index: most_important(self.index, other.index),
+                worktree: most_important(self.worktree, other.worktree),
+            },
+            (true, false) => self,
+            (false, true) => other,
+            (false, false) if self.untracked() => self,
+            (false, false) => other,
+        }
+    }
+
+    /// The status as shown in the column, `-` for an unchanged side
+    pub fn text(&self) -> String {
+        [self.index, self.worktree]
+            .iter()
+            .map(|c| if *c == ' ' { '-' } else { *c })
+            .collect()
+    }
+
+    /// The status colored like git does, staged changes in green and the others in red
+    pub fn painted(&self, colored: bool) -> String {
+        if !colored {
+            return self.text();
+        }
+        let text: Vec<char> = self.text().chars().collect();
+        let (index_color, worktree_color) = match (self.untracked(), self.ignored()) {
+            (true, _) => (Color::DarkRed, Color::DarkRed),
+            (_, true) => (Color::DarkGrey, Color::DarkGrey),
+            _ => (Color::DarkGreen, Color::DarkRed),
+        };
+        let paint = |c: char, color: Color| match c {
+            '-' => c.to_string(),
+            c => foreground(color).apply(c).to_string(),
+        };
+        format!(
+            "{}{}",
+            paint(text[0], index_color),
+            paint(text[1], worktree_color)
+        )
+    }
+
+    /// Style for the name of a changed file
+    pub fn name_style(&self) -> Option<ContentStyle> {
+        let color = if self.ignored() {
+            Color::DarkGrey
+        } else if self.index == 'U' || self.worktree == 'U' {
+            Color::Red
+        } else if self.untracked() || self.index == 'A' {
+            Color::Green
+        } else if self.index != ' ' || self.worktree != ' ' {
+            Color::Yellow
+        } else {
+            return None;
+        };
+        Some(foreground(color))
+    }
+}
+
+fn foreground(color: Color) -> ContentStyle {
+    ContentStyle {
+        foreground_color: Some(color),
+        ..ContentStyle::default()
+    }
+}
+
+/// Unchanged files, and files outside of a repository
+static UNCHANGED: Status = Status {
+    index: ' ',
+    worktree: ' ',
+};
+
+/// Order of the kinds of change when aggregating them in a directory
+fn most_important(a: char, b: char) -> char {
+    static ORDER: &str = " TCRADMU";
+    let rank = |c: char| ORDER.find(c).unwrap_or(0);
+    if rank(a) >= rank(b) {
+        a
+    } else {
+        b
+    }
+}
+
+/// Read the status of the repositories the operands are in
+pub fn load(operands: &[PathBuf]) {
+    STATUSES.get_or_init(|| {
+        let mut statuses = Statuses::default();
+        for operand in operands {
+            statuses.read(operand);
+        }
+        statuses
+    });
+}
+
+/// Status of the file, the status of its directory when git reports the directory
+/// as a whole, and unchanged otherwise
+pub fn status(path: &Path) -> Status {
+    let Some(statuses) = STATUSES.get() else {
+        return UNCHANGED;
+    };
+    if let Some(status) = statuses.files.get(path) {
+        return *status;
+    }
+    path.ancestors()
+        .find_map(|dir| statuses.dirs.get(dir))
+        .copied()
+        .unwrap_or(UNCHANGED)
+}
+
+impl Statuses {
+    /// Add the status of everything below the operand
+    fn read(&mut self, operand: &Path) {
+        let dir = match operand.is_dir() {
+            true => operand,
+            false => operand
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new(".")),
+        };
+        let Some(root) = git(
+            dir,
+            &[OsStr::new("rev-parse"), OsStr::new("--show-toplevel")],
+        ) else {
+            trace!("Not in a git repository: {:?}", operand);
+            return;
+        };
+        let root = PathBuf::from(OsStr::from_bytes(root.trim_ascii_end()));
+        let (Ok(absolute), Ok(root)) = (operand.canonicalize(), root.canonicalize()) else {
+            return;
+        };
+        let Ok(relative) = absolute.strip_prefix(&root) else {
+            return;
+        };
+
+        let args = [
+            OsStr::new("status"),
+            OsStr::new("--porcelain=v1"),
+            OsStr::new("-z"),
+            OsStr::new("--ignored=matching"),
+            OsStr::new("--untracked-files=all"),
+            OsStr::new("--"),
+            absolute.as_os_str(),
+        ];
+        let Some(output) = git(dir, &args) else {
+            return;
+        };
+
+        self.add_output(operand, relative, &output);
+    }
+
+    /// Add the status entries below the operand, which is at `relative` in the repository
+    fn add_output(&mut self, operand: &Path, relative: &Path, output: &[u8]) {
+        for (status, path) in parse_porcelain(output) {
+            let Ok(below) = path.strip_prefix(relative) else {
+                continue;
+            };
+            let listed = operand.join(below);
+            if path.as_os_str().as_bytes().ends_with(b"/") {
+                self.dirs.insert(listed.clone(), status);
+            }
+            self.add(operand, listed, status);
+        }
+    }
+
+    /// Store the status of the file and merge it into the directories above it
+    fn add(&mut self, operand: &Path, path: PathBuf, status: Status) {
+        if !status.ignored() {
+            for dir in path.ancestors().skip(1) {
+                if !dir.starts_with(operand) {
+                    break;
+                }
+                let merged = match self.files.get(dir) {
+                    Some(existing) => existing.merge(status),
+                    None => status,
+                };
+                self.files.insert(dir.to_path_buf(), merged);
+            }
+        }
+        self.files.insert(path, status);
+    }
+}
+
+/// Parse the output of `git status --porcelain -z` into the status of each path,
+/// relative to the root of the repository, where directories end with a `/`
+fn parse_porcelain(output: &[u8]) -> Vec<(Status, &Path)> {
+    let mut entries = Vec::new();
+    /* Entries are `XY path`, renames and copies are followed by the old path */
+    let mut fields = output.split(|b| *b == 0).filter(|f| !f.is_empty());
+    while let Some(field) = fields.next() {
+        if field.len() < 4 {
+            continue;
+        }
+        let status = Status {
+            index: field[0] as char,
+            worktree: field[1] as char,
+        };
+        if matches!(status.index, 'R' | 'C') {
+            fields.next();
+        }
+        entries.push((status, Path::new(OsStr::from_bytes(&field[3..]))));
+    }
+    entries
+}
+
+/// Run git in the directory, None when it fails
+fn git(dir: &Path, args: &[&OsStr]) -> Option<Vec<u8>> {
+    let output = match Command::new("git").arg("-C").arg(dir).args(args).output() {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Failed to run git: {}", e);
+            return None;
+        }
+    };
+    match output.status.success() {
+        true => Some(output.stdout),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(xy: &str) -> Status {
+        let mut chars = xy.chars();
+        Status {
+            index: chars.next().unwrap(),
+            worktree: chars.next().unwrap(),
+        }
+    }
+
+    static OUTPUT: &[u8] =
+        b" M src/main.rs\0R  src/new.rs\0src/old.rs\0?? src/notes.txt\0!! target/\0A  lib.rs\0";
+
+    #[test]
+    fn parse_entries() {
+        let entries = parse_porcelain(OUTPUT);
+        let expected = [
+            (" M", "src/main.rs"),
+            /* The old path of the rename is skipped */
+            ("R ", "src/new.rs"),
+            ("??", "src/notes.txt"),
+            ("!!", "target/"),
+            ("A ", "lib.rs"),
+        ];
+        assert_eq!(entries.len(), expected.len());
+        for ((parsed, path), (xy, expected_path)) in entries.iter().zip(expected) {
+            assert_eq!(*parsed, status(xy));
+            assert_eq!(path.as_os_str(), expected_path);
+        }
+        assert!(parse_porcelain(b"").is_empty());
+    }
+
+    #[test]
+    fn statuses_below_the_operand() {
+        let mut statuses = Statuses::default();
+        statuses.add_output(Path::new("repo"), Path::new(""), OUTPUT);
+        let files = &statuses.files;
+        assert_eq!(files[Path::new("repo/src/main.rs")], status(" M"));
+        assert_eq!(files[Path::new("repo/src/new.rs")], status("R "));
+        assert!(!files.contains_key(Path::new("repo/src/old.rs")));
+        /* Ignored directories give their status to everything below them */
+        assert_eq!(statuses.dirs[Path::new("repo/target")], status("!!"));
+
+        /* Tracked changes take precedence over untracked files */
+        assert_eq!(files[Path::new("repo/src")], status("RM"));
+        assert_eq!(files[Path::new("repo")], status("AM"));
+        assert!(!files.contains_key(Path::new("")));
+
+        /* Only the entries below an operand in a subdirectory are added */
+        let mut statuses = Statuses::default();
+        statuses.add_output(Path::new("."), Path::new("src"), OUTPUT);
+        assert_eq!(statuses.files[Path::new("./notes.txt")], status("??"));
+        assert_eq!(statuses.files.len(), 4);
+    }
+
+    #[test]
+    fn merge_precedence() {
+        assert_eq!(status("??").merge(status(" M")), status(" M"));
+        assert_eq!(status(" M").merge(status("??")), status(" M"));
+        assert_eq!(status("!!").merge(status("??")), status("??"));
+        assert_eq!(status("??").merge(status("!!")), status("??"));
+        assert_eq!(status("A ").merge(status(" M")), status("AM"));
+        assert_eq!(status("M ").merge(status("A ")), status("M "));
+        assert_eq!(status(" D").merge(status("UU")), status("UU"));
+    }
+
+    #[test]
+    fn status_text() {
+        assert_eq!(status(" M").text(), "-M");
+        assert_eq!(status("  ").text(), "--");
+        assert_eq!(status("??").painted(false), "??");
+    }
+}
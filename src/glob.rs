@@ -0,0 +1,199 @@
+/// Check if the name matches the shell pattern, with `*`, `?`, `[...]` and
+/// backslash escapes
+pub fn matches(pattern: &str, name: &str) -> bool {
+    /* Like the shell, a leading dot is only matched by a dot */
+    if name.starts_with('.') && !(pattern.starts_with('.') || pattern.starts_with("\\.")) {
+        return false;
+    }
+    fnmatch(pattern, name)
+}
+
+/// Check if the relative path matches the pattern, where wildcards do not match
+/// a `/` and `**` matches any number of directories
+pub fn matches_path(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_components(&pattern, &path)
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| match_components(rest, &path[i..])),
+        Some((component, rest)) => {
+            !path.is_empty() && fnmatch(component, path[0]) && match_components(rest, &path[1..])
+        }
+    }
+}
+
+/// Match a single name against the pattern, where wildcards also match a leading dot
+fn fnmatch(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let mut p = 0;
+    let mut n = 0;
+    /* Where to continue after the last `*` when the rest does not match */
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match bracket(&pattern[p..], name[n]) {
+                Some((true, len)) => Some(p + len),
+                Some((false, _)) => None,
+                /* Without a closing bracket the `[` is a plain character */
+                None if name[n] == '[' => Some(p + 1),
+                None => None,
+            },
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == name[n]).then_some(p + 2),
+            Some(c) => (*c == name[n]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            /* Let the last `*` take one more character and try again */
+            (None, Some((star_p, star_n))) => {
+                backtrack = Some((star_p, star_n + 1));
+                p = star_p;
+                n = star_n + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match the character against the bracket expression at the start of the pattern,
+/// giving if it matched and the length of the expression, None when it is not closed
+fn bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(pattern.get(i), Some('!' | '^'));
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let start = *pattern.get(i)?;
+        /* A `]` right after the opening bracket is part of the set */
+        if start == ']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
+
+        let (start, len) = match start {
+            '\\' => (*pattern.get(i + 1)?, 2),
+            start => (start, 1),
+        };
+        i += len;
+
+        if pattern.get(i) == Some(&'-') && pattern.get(i + 1).is_some_and(|end| *end != ']') {
+            let (end, len) = match pattern[i + 1] {
+                '\\' => (*pattern.get(i + 2)?, 3),
+                end => (end, 2),
+            };
+            i += len;
+            matched |= start <= c && c <= end;
+        } else {
+            matched |= start == c;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", ".rs.rs"));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(!matches("a*b*c", "aXXbYY"));
+        assert!(matches("*", "anything"));
+        assert!(!matches("*.rs", "main.rsx"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_char() {
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?.txt", "ab.txt"));
+        assert!(!matches("?.txt", ".txt"));
+        assert!(matches("f??", "föo"));
+    }
+
+    #[test]
+    fn brackets() {
+        assert!(matches("[abc]x", "bx"));
+        assert!(!matches("[abc]x", "dx"));
+        assert!(matches("file[0-9]", "file7"));
+        assert!(!matches("file[0-9]", "filea"));
+        assert!(matches("[!a-c]", "d"));
+        assert!(!matches("[!a-c]", "b"));
+        assert!(matches("[^a-c]", "d"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[!]]", "a"));
+        assert!(!matches("[!]]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[\\]]", "]"));
+    }
+
+    #[test]
+    fn unclosed_bracket_is_literal() {
+        assert!(matches("a[b", "a[b"));
+        assert!(!matches("a[b", "ab"));
+        assert_eq!(bracket(&['[', 'a', 'b'], 'a'), None);
+    }
+
+    #[test]
+    fn bracket_length() {
+        let pattern: Vec<char> = "[a-z]rest".chars().collect();
+        assert_eq!(bracket(&pattern, 'q'), Some((true, 5)));
+        assert_eq!(bracket(&pattern, 'Q'), Some((false, 5)));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("a\\?", "a?"));
+        assert!(!matches("a\\?", "ab"));
+    }
+
+    #[test]
+    fn leading_dot_needs_a_dot() {
+        assert!(!matches("*", ".hidden"));
+        assert!(!matches("?hidden", ".hidden"));
+        assert!(!matches("[.]hidden", ".hidden"));
+        assert!(matches(".*", ".hidden"));
+        assert!(matches("\\.hidden", ".hidden"));
+        assert!(matches(".*~", ".file~"));
+        /* Only the start of the name is special */
+        assert!(matches("a*", "a.b"));
+        assert!(fnmatch("*", ".hidden"));
+    }
+
+    #[test]
+    fn paths() {
+        assert!(matches_path("doc/*.txt", "doc/a.txt"));
+        assert!(!matches_path("doc/*.txt", "doc/sub/m.txt"));
+        assert!(matches_path("**/build", "build"));
+        assert!(matches_path("**/build", "x/y/build"));
+        assert!(!matches_path("build", "x/build"));
+        assert!(matches_path("doc/**/m.txt", "doc/m.txt"));
+        assert!(matches_path("doc/**/m.txt", "doc/a/b/m.txt"));
+        assert!(matches_path("doc/**", "doc/a/b"));
+        assert!(!matches_path("*", "a/b"));
+    }
+}
@@ -0,0 +1,366 @@
+use log::{trace, warn};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::{glob, LsArgs};
+
+/// Ignore files read in every directory, later ones take precedence
+static DIR_IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// A single line of an ignore file
+struct Rule {
+    /// Pattern matched against the whole path below the directory of the file
+    pattern: String,
+    /// `!pattern`, which lists a file that an earlier pattern ignored
+    negated: bool,
+    /// `pattern/`, which only matches directories
+    dir_only: bool,
+}
+
+/// Rules of the ignore files that apply to the same directory
+pub struct Ignore {
+    /// Directory the patterns are relative to, as the path the listing reads it by
+    anchor: PathBuf,
+    /// Path of the anchor below the directory of the ignore files, for the files
+    /// above the listed directory
+    prefix: PathBuf,
+    /// Rules in the order they were read, the last matching one decides
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    fn new(anchor: &Path, prefix: PathBuf) -> Ignore {
+        Ignore {
+            anchor: anchor.to_path_buf(),
+            prefix,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Rules from outside the listed directory: the files given with --ignore-file,
+    /// and with --gitignore the global excludes, the exclude file of the repository
+    /// and the ignore files of the directories above the operand in the repository
+    pub fn for_operand(path: &Path, args: &LsArgs) -> Vec<Ignore> {
+        let mut custom = Ignore::new(path, PathBuf::new());
+        for file in args.ignore_file.iter() {
+            custom.read(file);
+        }
+        let mut ignores = vec![custom];
+        if !args.gitignore {
+            return ignores;
+        }
+
+        let Ok(absolute) = path.canonicalize() else {
+            return ignores;
+        };
+        let Some(root) = absolute.ancestors().find(|dir| dir.join(".git").exists()) else {
+            return ignores;
+        };
+        let prefix_of = |dir: &Path| {
+            absolute
+                .strip_prefix(dir)
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        };
+
+        let mut repository = Ignore::new(path, prefix_of(root));
+        if let Some(file) = global_excludes_file() {
+            repository.read(&file);
+        }
+        repository.read(&root.join(".git").join("info").join("exclude"));
+        ignores.push(repository);
+
+        /* From the root of the repository down to the parent of the operand */
+        let mut parents: Vec<&Path> = absolute
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(root))
+            .collect();
+        parents.reverse();
+        for dir in parents {
+            let mut ignore = Ignore::new(path, prefix_of(dir));
+            ignore.read_dir_files(dir);
+            ignores.push(ignore);
+        }
+        ignores
+    }
+
+    /// Rules from the ignore files in the directory, with --gitignore
+    pub fn for_dir(path: &Path, args: &LsArgs) -> Ignore {
+        let mut ignore = Ignore::new(path, PathBuf::new());
+        if args.gitignore {
+            ignore.read_dir_files(path);
+        }
+        ignore
+    }
+
+    fn read_dir_files(&mut self, dir: &Path) {
+        for name in DIR_IGNORE_FILES {
+            self.read(&dir.join(name));
+        }
+    }
+
+    /// Add the rules of the ignore file, a missing file has no rules
+    fn read(&mut self, file: &Path) {
+        match std::fs::read(file) {
+            Ok(content) => {
+                trace!("Read ignore file: {:?}", file);
+                let content = String::from_utf8_lossy(&content);
+                self.rules.extend(content.lines().filter_map(parse_line));
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to read ignore file {:?}: {}", file, e),
+        }
+    }
+
+    /// Some(true) when the last matching rule ignores the path, Some(false) when it
+    /// lists it again, and None when no rule matches
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let relative = self.prefix.join(path.strip_prefix(&self.anchor).ok()?);
+        let relative = relative.to_string_lossy();
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| glob::matches_path(&rule.pattern, &relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Check if the path is ignored, where the rules of deeper directories take
+/// precedence over the ones above them
+pub fn is_ignored(ignores: &[&Ignore], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.matched(path, is_dir))
+        .unwrap_or(false)
+}
+
+/// Parse a line of a gitignore file, None for blank lines and comments
+fn parse_line(line: &str) -> Option<Rule> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    /* Trailing spaces are removed unless they are escaped */
+    let trimmed = line.trim_end_matches(' ');
+    let line = match trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        true => &line[..trimmed.len() + 1],
+        false => trimmed,
+    };
+
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    if line.is_empty() {
+        return None;
+    }
+
+    /* A pattern with a slash is relative to the directory of the ignore file,
+     * otherwise it matches at any depth below it */
+    let pattern = match line.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if line.contains('/') => line.to_string(),
+        None => format!("**/{}", line),
+    };
+    Some(Rule {
+        pattern,
+        negated,
+        dir_only,
+    })
+}
+
+/// The core.excludesFile of the git configuration, or the default global ignore file
+fn global_excludes_file() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+
+    /* The configuration in the home directory takes precedence */
+    let configs = [
+        home.as_ref().map(|home| home.join(".gitconfig")),
+        config_home
+            .as_ref()
+            .map(|dir| dir.join("git").join("config")),
+    ];
+    let configured = configs
+        .iter()
+        .flatten()
+        .filter_map(|config| std::fs::read_to_string(config).ok())
+        .find_map(|config| excludes_file_setting(&config));
+
+    match configured {
+        Some(file) => match (file.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(PathBuf::from(file)),
+        },
+        None => config_home.map(|dir| dir.join("git").join("ignore")),
+    }
+}
+
+/// Value of excludesFile in the [core] section of a git configuration
+fn excludes_file_setting(config: &str) -> Option<String> {
+    let mut in_core = false;
+    let mut value = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_core = line.to_ascii_lowercase().starts_with("[core]");
+        } else if let Some((key, setting)) = line.split_once('=') {
+            if in_core && key.trim().eq_ignore_ascii_case("excludesfile") {
+                value = Some(setting.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rules of an ignore file in `dir`, with the anchor at `dir` itself
+    fn ignore(lines: &[&str]) -> Ignore {
+        let mut ignore = Ignore::new(Path::new("dir"), PathBuf::new());
+        ignore.rules = lines.iter().filter_map(|line| parse_line(line)).collect();
+        ignore
+    }
+
+    fn ignored(ignore: &Ignore, path: &str, is_dir: bool) -> bool {
+        is_ignored(&[ignore], Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn parse_skips_blanks_and_comments() {
+        assert!(parse_line("").is_none());
+        assert!(parse_line("# comment").is_none());
+        assert!(parse_line("   ").is_none());
+        assert!(parse_line("/").is_none());
+        assert!(parse_line("!").is_none());
+    }
+
+    #[test]
+    fn parse_anchoring() {
+        assert_eq!(parse_line("*.log").unwrap().pattern, "**/*.log");
+        assert_eq!(parse_line("/build").unwrap().pattern, "build");
+        assert_eq!(parse_line("doc/*.txt").unwrap().pattern, "doc/*.txt");
+        assert_eq!(parse_line("**/foo").unwrap().pattern, "**/foo");
+    }
+
+    #[test]
+    fn parse_negation_and_directories() {
+        let rule = parse_line("!keep.log").unwrap();
+        assert!(rule.negated);
+        assert!(!rule.dir_only);
+        assert_eq!(rule.pattern, "**/keep.log");
+
+        let rule = parse_line("target/").unwrap();
+        assert!(!rule.negated);
+        assert!(rule.dir_only);
+        assert_eq!(rule.pattern, "**/target");
+    }
+
+    #[test]
+    fn parse_escapes_and_trailing_spaces() {
+        assert_eq!(parse_line("\\#file").unwrap().pattern, "**/\\#file");
+        assert_eq!(parse_line("name  ").unwrap().pattern, "**/name");
+        assert_eq!(parse_line("name\\ ").unwrap().pattern, "**/name\\ ");
+        assert_eq!(parse_line("name\\  ").unwrap().pattern, "**/name\\ ");
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let rules = ignore(&["*.log", "!keep.log"]);
+        assert!(ignored(&rules, "dir/a.log", false));
+        assert!(ignored(&rules, "dir/sub/a.log", false));
+        assert!(!ignored(&rules, "dir/keep.log", false));
+        assert!(!ignored(&rules, "dir/a.txt", false));
+
+        let rules = ignore(&["!keep.log", "*.log"]);
+        assert!(ignored(&rules, "dir/keep.log", false));
+    }
+
+    #[test]
+    fn anchored_patterns() {
+        let rules = ignore(&["/build"]);
+        assert!(ignored(&rules, "dir/build", true));
+        assert!(!ignored(&rules, "dir/x/build", true));
+
+        let rules = ignore(&["build"]);
+        assert!(ignored(&rules, "dir/build", true));
+        assert!(ignored(&rules, "dir/x/build", true));
+
+        let rules = ignore(&["doc/*.txt"]);
+        assert!(ignored(&rules, "dir/doc/a.txt", false));
+        assert!(!ignored(&rules, "dir/doc/sub/m.txt", false));
+    }
+
+    #[test]
+    fn directory_only_patterns() {
+        let rules = ignore(&["target/"]);
+        assert!(ignored(&rules, "dir/target", true));
+        assert!(!ignored(&rules, "dir/target", false));
+    }
+
+    #[test]
+    fn escaped_names() {
+        let rules = ignore(&["\\#file", "name\\ "]);
+        assert!(ignored(&rules, "dir/#file", false));
+        assert!(ignored(&rules, "dir/name ", false));
+        assert!(!ignored(&rules, "dir/name", false));
+    }
+
+    #[test]
+    fn parent_directory_prefix() {
+        /* A .gitignore in the parent of the listed directory `dir` */
+        let mut rules = ignore(&["/dir/out", "sub/*.o"]);
+        rules.prefix = PathBuf::from("dir");
+        assert!(ignored(&rules, "dir/out", true));
+        assert!(!ignored(&rules, "dir/sub/a.o", false));
+        assert!(!ignored(&rules, "dir/x/out", true));
+
+        let mut rules = ignore(&["/dir/sub/*.o"]);
+        rules.prefix = PathBuf::from("dir");
+        assert!(ignored(&rules, "dir/sub/a.o", false));
+    }
+
+    #[test]
+    fn deeper_rules_take_precedence() {
+        let parent = ignore(&["*.log"]);
+        let child = ignore(&["!keep.log"]);
+        assert!(!is_ignored(
+            &[&parent, &child],
+            Path::new("dir/keep.log"),
+            false
+        ));
+        assert!(is_ignored(
+            &[&parent, &child],
+            Path::new("dir/a.log"),
+            false
+        ));
+        assert!(is_ignored(
+            &[&child, &parent],
+            Path::new("dir/keep.log"),
+            false
+        ));
+    }
+
+    #[test]
+    fn core_excludes_file_setting() {
+        let config = "[user]\n\tname = x\n[core]\n\texcludesFile = \"~/.gitignore_global\"\n";
+        assert_eq!(
+            excludes_file_setting(config).as_deref(),
+            Some("~/.gitignore_global")
+        );
+        assert_eq!(excludes_file_setting("[other]\nexcludesfile = x\n"), None);
+    }
+}
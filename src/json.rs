@@ -0,0 +1,253 @@
+use crossterm::{queue, style};
+use std::os::unix::fs::MetadataExt;
+
+use crate::error::LsError;
+use crate::owners;
+use crate::time::{self, TimeKind};
+use crate::{FSFile, FSFileType, Skipped};
+
+/// Print the whole tree as a single nested JSON object, or an array of them
+/// when there are several operands
+pub fn print_json<W>(w: &mut W, files: &[FSFile], dirs: &[FSFile]) -> Result<(), LsError>
+where
+    W: std::io::Write,
+{
+    let mut out = String::with_capacity(4096);
+    let operands: Vec<&FSFile> = files.iter().chain(dirs.iter()).collect();
+    match operands.as_slice() {
+        [fs_file] => write_tree(&mut out, fs_file),
+        _ => {
+            out.push('[');
+            for (i, fs_file) in operands.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_tree(&mut out, fs_file);
+            }
+            out.push(']');
+        }
+    }
+    out.push('\n');
+    queue!(w, style::Print(out))?;
+    Ok(())
+}
+
+/// Print one flat JSON object per line for every operand and every entry below
+/// the directory operands, each directory before its entries
+pub fn print_ndjson<W>(w: &mut W, files: &[FSFile], dirs: &[FSFile]) -> Result<(), LsError>
+where
+    W: std::io::Write,
+{
+    let mut stack: Vec<&FSFile> = dirs.iter().rev().collect();
+    stack.extend(files.iter().rev());
+
+    while let Some(entry) = stack.pop() {
+        let mut out = String::with_capacity(256);
+        write_fields(&mut out, entry);
+        out.push_str("}\n");
+        queue!(w, style::Print(out))?;
+
+        if let FSFileType::Dir(dir_type) = &entry.entry_type {
+            stack.extend(dir_type.childs.iter().rev());
+        }
+    }
+    Ok(())
+}
+
+/// Write the entry and everything below it as nested objects, keeping the
+/// directories being written on the heap instead of recursing
+fn write_tree(out: &mut String, fs_file: &FSFile) {
+    let mut stack = Vec::new();
+    write_fields(out, fs_file);
+    match &fs_file.entry_type {
+        /* Directories that were not read have no children, not an empty list */
+        FSFileType::Dir(dir_type) if dir_type.read => {
+            out.push(',');
+            write_key(out, "children");
+            out.push('[');
+            stack.push(dir_type.childs.iter().enumerate());
+        }
+        _ => out.push('}'),
+    }
+
+    while let Some(it) = stack.last_mut() {
+        let Some((i, child)) = it.next() else {
+            /* Close the children and the directory they are in */
+            out.push_str("]}");
+            stack.pop();
+            continue;
+        };
+
+        if i > 0 {
+            out.push(',');
+        }
+        write_fields(out, child);
+        match &child.entry_type {
+            FSFileType::Dir(dir_type) if dir_type.read => {
+                out.push(',');
+                write_key(out, "children");
+                out.push('[');
+                stack.push(dir_type.childs.iter().enumerate());
+            }
+            _ => out.push('}'),
+        }
+    }
+}
+
+/// Write the opening brace and the fields of the entry, leaving the object open
+fn write_fields(out: &mut String, fs_file: &FSFile) {
+    let Some(metadata) = &fs_file.metadata else {
+        /* Only the name is known of an entry that could not be accessed */
+        write_inaccessible(out, fs_file);
+        return;
+    };
+    let file_type = match fs_file.entry_type {
+        FSFileType::File => "file",
+        FSFileType::Dir(_) => "directory",
+        FSFileType::Symlink(_) => "symlink",
+        FSFileType::Fifo => "fifo",
+        FSFileType::Socket => "socket",
+        FSFileType::BlockDevice => "block_device",
+        FSFileType::CharDevice => "char_device",
+        FSFileType::Inaccessible(_) => "unknown",
+    };
+    let user = owners::lookup_user(metadata.uid());
+    let group = owners::lookup_group(metadata.gid());
+
+    out.push('{');
+    write_key(out, "name");
+    write_string(out, &fs_file.name.to_string_lossy());
+    out.push(',');
+    write_key(out, "path");
+    write_string(out, &fs_file.path_buf.to_string_lossy());
+    out.push(',');
+    write_key(out, "type");
+    write_string(out, file_type);
+    out.push(',');
+    write_key(out, "size");
+    out.push_str(&metadata.size().to_string());
+    out.push(',');
+    write_key(out, "mode");
+    write_string(out, &format!("{:04o}", metadata.mode() & 0o7777));
+    out.push(',');
+    write_key(out, "uid");
+    out.push_str(&metadata.uid().to_string());
+    out.push(',');
+    write_key(out, "gid");
+    out.push_str(&metadata.gid().to_string());
+    out.push(',');
+    write_key(out, "user");
+    match &user {
+        Some(name) => write_string(out, name),
+        None => out.push_str("null"),
+    }
+    out.push(',');
+    write_key(out, "group");
+    match &group {
+        Some(name) => write_string(out, name),
+        None => out.push_str("null"),
+    }
+    out.push(',');
+    write_key(out, "accessed");
+    out.push_str(&metadata.atime().to_string());
+    out.push(',');
+    write_key(out, "modified");
+    out.push_str(&metadata.mtime().to_string());
+    out.push(',');
+    write_key(out, "changed");
+    out.push_str(&metadata.ctime().to_string());
+    out.push(',');
+    write_key(out, "created");
+    match time::timestamp(metadata, TimeKind::Birth) {
+        Some((secs, _)) => out.push_str(&secs.to_string()),
+        None => out.push_str("null"),
+    }
+
+    if let FSFileType::Symlink(link) = &fs_file.entry_type {
+        out.push(',');
+        write_key(out, "target");
+        write_string(out, &link.target.to_string_lossy());
+        out.push(',');
+        write_key(out, "broken");
+        out.push_str(&link.target_metadata.is_none().to_string());
+    }
+
+    if let FSFileType::Dir(dir_type) = &fs_file.entry_type {
+        match dir_type.skipped {
+            Some(Skipped::Recursive) => {
+                out.push(',');
+                write_key(out, "error");
+                write_string(out, Skipped::Recursive.note());
+            }
+            Some(Skipped::MountPoint) => {
+                out.push(',');
+                write_key(out, "mount_point");
+                out.push_str("true");
+            }
+            None => {}
+        }
+        if let Some(error) = &dir_type.error {
+            out.push(',');
+            write_key(out, "error");
+            write_string(out, &error.note());
+        }
+        if let Some(total) = &dir_type.total {
+            out.push(',');
+            write_key(out, "total_size");
+            out.push_str(&total.apparent.to_string());
+            out.push(',');
+            write_key(out, "total_allocated");
+            out.push_str(&total.allocated.to_string());
+        }
+    }
+
+    if let FSFileType::BlockDevice | FSFileType::CharDevice = fs_file.entry_type {
+        let (major, minor) = crate::device_numbers(metadata.rdev());
+        out.push(',');
+        write_key(out, "major");
+        out.push_str(&major.to_string());
+        out.push(',');
+        write_key(out, "minor");
+        out.push_str(&minor.to_string());
+    }
+}
+
+/// Write the name, the path and the error of an entry without metadata
+fn write_inaccessible(out: &mut String, fs_file: &FSFile) {
+    out.push('{');
+    write_key(out, "name");
+    write_string(out, &fs_file.name.to_string_lossy());
+    out.push(',');
+    write_key(out, "path");
+    write_string(out, &fs_file.path_buf.to_string_lossy());
+    out.push(',');
+    write_key(out, "type");
+    write_string(out, "unknown");
+    if let FSFileType::Inaccessible(inaccessible) = &fs_file.entry_type {
+        out.push(',');
+        write_key(out, "error");
+        write_string(out, &inaccessible.error.note());
+    }
+}
+
+fn write_key(out: &mut String, key: &str) {
+    write_string(out, key);
+    out.push(':');
+}
+
+/// Write a JSON string literal, escaping quotes and control characters
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
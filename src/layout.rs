@@ -0,0 +1,264 @@
+use crossterm::{queue, style, terminal};
+use std::io::IsTerminal;
+
+use crate::error::LsError;
+
+/// Spaces between two columns in the grid
+static COLUMN_SEPARATOR: usize = 2;
+
+/// How the names of a short listing are placed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// One name per line, `-1`
+    OnePerLine,
+    /// Columns sorted down then across, `-C`
+    Vertical,
+    /// Columns sorted across then down, `-x`
+    Across,
+}
+
+/// A piece of output together with its width on the terminal
+pub struct Cell {
+    pub text: String,
+    pub width: usize,
+}
+
+impl Cell {
+    /// Create a cell from the styled text and the unstyled text it displays
+    pub fn new(text: String, plain: &str) -> Cell {
+        Cell {
+            text,
+            width: display_width(plain),
+        }
+    }
+
+    /// Put unstyled text after the cell
+    pub fn append(&mut self, suffix: &str) {
+        self.text.push_str(suffix);
+        self.width += display_width(suffix);
+    }
+
+    /// Put unstyled text in front of the cell
+    pub fn prepend(&mut self, prefix: &str) {
+        self.text.insert_str(0, prefix);
+        self.width += display_width(prefix);
+    }
+}
+
+/// Width of the terminal, from COLUMNS, the terminal or the default of 80
+pub fn terminal_width() -> usize {
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+    {
+        return columns;
+    }
+
+    if std::io::stdout().is_terminal() {
+        if let Ok((columns, _)) = terminal::size() {
+            return columns as usize;
+        }
+    }
+    80
+}
+
+/// Number of terminal columns needed to display the string
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        /* Control characters and combining marks take no space */
+        0x00..=0x1f | 0x7f..=0x9f => 0,
+        0x0300..=0x036f | 0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x20d0..=0x20ff => 0,
+        0x200b..=0x200f | 0xfe00..=0xfe0f | 0xfe20..=0xfe2f => 0,
+        /* East asian wide and full width characters */
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x2fffd
+        | 0x30000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// Print the cells in the given format, fitting the grid to `width` columns
+pub fn print_cells<W>(
+    w: &mut W,
+    cells: &[Cell],
+    format: Format,
+    width: usize,
+) -> Result<(), LsError>
+where
+    W: std::io::Write,
+{
+    if cells.is_empty() {
+        return Ok(());
+    }
+
+    let (rows, col_widths) = match format {
+        Format::OnePerLine => (cells.len(), vec![0]),
+        Format::Vertical | Format::Across => grid_size(cells, format, width),
+    };
+    let cols = col_widths.len();
+
+    for row in 0..rows {
+        let mut line = String::with_capacity(width);
+        for (col, col_width) in col_widths.iter().enumerate() {
+            let Some(cell) = cells.get(cell_index(format, rows, cols, row, col)) else {
+                break;
+            };
+            line.push_str(cell.text.as_str());
+
+            /* Pad all but the last name of the row */
+            let next = cell_index(format, rows, cols, row, col + 1);
+            if col + 1 < cols && next < cells.len() {
+                let padding = col_width.saturating_sub(cell.width) + COLUMN_SEPARATOR;
+                line.extend(std::iter::repeat_n(' ', padding));
+            }
+        }
+        line.push('\n');
+        queue!(w, style::Print(line))?;
+    }
+    Ok(())
+}
+
+fn cell_index(format: Format, rows: usize, cols: usize, row: usize, col: usize) -> usize {
+    match format {
+        Format::Across => row * cols + col,
+        Format::OnePerLine | Format::Vertical => col * rows + row,
+    }
+}
+
+/// Find the layout with the fewest rows that fits the width, and the width of each column
+fn grid_size(cells: &[Cell], format: Format, width: usize) -> (usize, Vec<usize>) {
+    let count = cells.len();
+    /* Sorted across, a row count can be reached with several column counts,
+     * so try the columns instead to find the widest grid that fits */
+    let grids: Vec<(usize, usize)> = match format {
+        Format::Across => (1..=count)
+            .rev()
+            .map(|cols| (count.div_ceil(cols), cols))
+            .collect(),
+        Format::OnePerLine | Format::Vertical => (1..=count)
+            .map(|rows| (rows, count.div_ceil(rows)))
+            .collect(),
+    };
+
+    for (rows, cols) in grids {
+        let mut col_widths = vec![0; cols];
+        for row in 0..rows {
+            for (col, col_width) in col_widths.iter_mut().enumerate() {
+                if let Some(cell) = cells.get(cell_index(format, rows, cols, row, col)) {
+                    *col_width = (*col_width).max(cell.width);
+                }
+            }
+        }
+
+        let total = col_widths.iter().sum::<usize>() + COLUMN_SEPARATOR * (cols.saturating_sub(1));
+        if total <= width {
+            return (rows, col_widths);
+        }
+    }
+
+    /* Nothing fits, so fall back on a single column */
+    (count, vec![0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(names: &[&str]) -> Vec<Cell> {
+        names
+            .iter()
+            .map(|name| Cell::new(name.to_string(), name))
+            .collect()
+    }
+
+    fn printed(names: &[&str], format: Format, width: usize) -> String {
+        let mut out = Vec::new();
+        print_cells(&mut out, &cells(names), format, width).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    static NAMES: [&str; 9] = [
+        "a.y", "adir", "b.z", "big", "c.x", "file1", "file10", "file9", "zdir",
+    ];
+
+    #[test]
+    fn vertical() {
+        assert_eq!(
+            printed(&NAMES, Format::Vertical, 30),
+            "a.y   b.z  c.x    file10  zdir\nadir  big  file1  file9\n"
+        );
+        assert_eq!(
+            printed(&NAMES, Format::Vertical, 20),
+            "a.y   big    file10\nadir  c.x    file9\nb.z   file1  zdir\n"
+        );
+    }
+
+    #[test]
+    fn across() {
+        /* Four columns fit, even though no two-row grid does */
+        assert_eq!(
+            printed(&NAMES, Format::Across, 30),
+            "a.y   adir   b.z     big\nc.x   file1  file10  file9\nzdir\n"
+        );
+        assert_eq!(grid_size(&cells(&NAMES), Format::Across, 30).0, 3);
+        assert_eq!(printed(&["a", "b", "c"], Format::Across, 80), "a  b  c\n");
+    }
+
+    #[test]
+    fn one_per_line() {
+        assert_eq!(
+            printed(&["a", "b", "c"], Format::OnePerLine, 80),
+            "a\nb\nc\n"
+        );
+    }
+
+    #[test]
+    fn overlong_name() {
+        let names = ["short", "a-name-that-is-longer-than-the-terminal", "x"];
+        for format in [Format::Vertical, Format::Across] {
+            assert_eq!(
+                printed(&names, format, 20),
+                "short\na-name-that-is-longer-than-the-terminal\nx\n"
+            );
+        }
+    }
+
+    #[test]
+    fn wide_characters() {
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+        /* Each name takes four columns, so two of them fit in ten */
+        assert_eq!(
+            printed(&["日本", "中文", "ab"], Format::Vertical, 10),
+            "日本  ab\n中文\n"
+        );
+        assert_eq!(
+            printed(&["日本", "中文", "ab"], Format::Across, 10),
+            "日本  中文\nab\n"
+        );
+    }
+
+    #[test]
+    fn cell_order() {
+        assert_eq!(cell_index(Format::Vertical, 3, 2, 1, 1), 4);
+        assert_eq!(cell_index(Format::Across, 3, 2, 1, 1), 3);
+        assert_eq!(cell_index(Format::OnePerLine, 3, 1, 2, 0), 2);
+    }
+}
@@ -0,0 +1,130 @@
+use crossterm::{queue, style};
+
+use crate::error::LsError;
+use crate::layout::Cell;
+use crate::ARGS;
+
+/// A column of the long listing
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Column {
+    Blocks,
+    Octal,
+    Mode,
+    User,
+    Group,
+    Size,
+    Time,
+    Git,
+    Name,
+}
+
+enum Align {
+    Left,
+    Right,
+}
+
+impl Column {
+    /// Title of the column in the header
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Blocks => "Blocks",
+            Column::Octal => "Octal",
+            Column::Mode => "Mode",
+            Column::User => "User",
+            Column::Group => "Group",
+            Column::Size => "Size",
+            Column::Time => ARGS.get().unwrap().time.header(),
+            Column::Git => "Git",
+            Column::Name => "Name",
+        }
+    }
+
+    /// Numbers are right aligned so that their digits line up
+    fn align(&self) -> Align {
+        match self {
+            Column::Blocks | Column::Size => Align::Right,
+            Column::Octal
+            | Column::Mode
+            | Column::User
+            | Column::Group
+            | Column::Time
+            | Column::Git
+            | Column::Name => Align::Left,
+        }
+    }
+}
+
+/// Columns to show in the long listing, in order
+pub fn columns() -> Vec<Column> {
+    let args = ARGS.get().unwrap();
+    let mut columns = Vec::with_capacity(8);
+    if args.size {
+        columns.push(Column::Blocks);
+    }
+    if args.octal {
+        columns.push(Column::Octal);
+    }
+    columns.push(Column::Mode);
+    if !args.no_owner {
+        columns.push(Column::User);
+    }
+    if !args.no_group {
+        columns.push(Column::Group);
+    }
+    columns.extend([Column::Size, Column::Time]);
+    if args.git {
+        columns.push(Column::Git);
+    }
+    columns.push(Column::Name);
+    columns
+}
+
+/// Print the header and the rows with each column padded to its widest cell
+pub fn print_table<W>(w: &mut W, columns: &[Column], rows: &[Vec<Cell>]) -> Result<(), LsError>
+where
+    W: std::io::Write,
+{
+    let header: Vec<Cell> = columns
+        .iter()
+        .map(|c| Cell::new(c.header().to_string(), c.header()))
+        .collect();
+
+    let mut widths = vec![0; columns.len()];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.width);
+        }
+    }
+
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = format_row(columns, &widths, row);
+        queue!(w, style::Print(line))?;
+    }
+    Ok(())
+}
+
+fn format_row(columns: &[Column], widths: &[usize], row: &[Cell]) -> String {
+    let mut line = String::with_capacity(128);
+    for (i, ((column, width), cell)) in columns.iter().zip(widths).zip(row).enumerate() {
+        let last = i + 1 == columns.len();
+        let padding = width.saturating_sub(cell.width);
+        match column.align() {
+            Align::Right => {
+                line.extend(std::iter::repeat_n(' ', padding));
+                line.push_str(cell.text.as_str());
+            }
+            Align::Left => {
+                line.push_str(cell.text.as_str());
+                /* Do not leave trailing spaces after the last column */
+                if !last {
+                    line.extend(std::iter::repeat_n(' ', padding));
+                }
+            }
+        }
+        if !last {
+            line.push(' ');
+        }
+    }
+    line.push('\n');
+    line
+}
@@ -1,51 +1,472 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use colors::LsColors;
 use crossterm::{queue, style};
+use error::LsError;
+use ignore::Ignore;
+use layout::{Cell, Format};
 use log::{error, trace, warn};
-use std::env;
-use std::fs::{DirEntry, Metadata, ReadDir};
-use std::io::Write;
-use std::os::unix::fs::MetadataExt;
-use std::path::PathBuf;
+use long::Column;
+use quoting::QuotingStyle;
+use size::SizeFormat;
+use sort::SortKey;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::{DirEntry, Metadata};
+use std::io::{IsTerminal, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
-use users::{Users, UsersCache};
+use time::{TimeKind, TimeStyle};
+
+mod colors;
+mod error;
+mod git;
+mod glob;
+mod ignore;
+mod json;
+mod layout;
+mod long;
+mod owners;
+mod permissions;
+mod quoting;
+mod size;
+mod sort;
+mod time;
 
 #[derive(Clone, Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, disable_help_flag = true)]
 struct LsArgs {
-    /// Directory to list
-    name: Option<String>,
+    /// Print help
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+
+    /// Files and directories to list
+    names: Vec<PathBuf>,
 
     /// List files
     #[arg(short, long)]
     list: bool,
 
-    /// List all files
+    /// Like -l, but list numeric user and group IDs
+    #[arg(short, long)]
+    numeric_uid_gid: bool,
+
+    /// Like -l, but do not list the group
+    #[arg(short = 'o')]
+    no_group: bool,
+
+    /// Like -l, but do not list the owner
+    #[arg(short = 'g')]
+    no_owner: bool,
+
+    /// List directories themselves, not their contents
     #[arg(short, long)]
+    directory: bool,
+
+    /// List all files, including . and ..
+    #[arg(short, long, overrides_with = "almost_all")]
     all: bool,
 
-    /// List files in a tree
-    #[arg(short, long, default_value_t = 1)]
-    tree: u8,
+    /// List all files except . and ..
+    #[arg(short = 'A', long, overrides_with = "all")]
+    almost_all: bool,
+
+    /// Do not list entries matching the shell PATTERN
+    #[arg(short = 'I', long, value_name = "PATTERN")]
+    ignore: Vec<String>,
+
+    /// Only list files matching the shell PATTERN, directories are always listed
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Also match directory names with --include, listing all of a matching directory
+    #[arg(long)]
+    matchdirs: bool,
+
+    /// Do not list directories without any files below them, after filtering
+    #[arg(long)]
+    prune: bool,
+
+    /// Do not list backups ending with ~
+    #[arg(short = 'B', long)]
+    ignore_backups: bool,
+
+    /// Do not list files ignored by .gitignore, .ignore and the git excludes
+    #[arg(long)]
+    gitignore: bool,
+
+    /// Do not list files matching the gitignore patterns in PATH
+    #[arg(long, value_name = "PATH")]
+    ignore_file: Vec<PathBuf>,
+
+    /// List files in a tree, all the way down
+    #[arg(long)]
+    tree: bool,
+
+    /// List files in a tree, N levels deep
+    #[arg(short = 't', long, value_name = "N")]
+    level: Option<usize>,
+
+    /// List entries by columns
+    #[arg(short = 'C', overrides_with_all = ["across", "one_per_line"])]
+    columns: bool,
+
+    /// List entries by lines instead of by columns
+    #[arg(short = 'x', overrides_with_all = ["columns", "one_per_line"])]
+    across: bool,
+
+    /// List one file per line
+    #[arg(short = '1', overrides_with_all = ["columns", "across"])]
+    one_per_line: bool,
+
+    /// Print sizes in powers of 1024, e.g. 1K 234M 2G
+    #[arg(short = 'h', long, overrides_with_all = ["si", "block_size"])]
+    human_readable: bool,
+
+    /// Print sizes in powers of 1000, e.g. 1k 234M 2G
+    #[arg(long, overrides_with_all = ["human_readable", "block_size"])]
+    si: bool,
+
+    /// Scale sizes by SIZE, e.g. 1024, K, 4KiB or MB
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = size::parse_block_size,
+        overrides_with_all = ["human_readable", "si"]
+    )]
+    block_size: Option<SizeFormat>,
+
+    /// Show the permissions in octal as well
+    #[arg(long)]
+    octal: bool,
+
+    /// Print the allocated size of each file in blocks
+    #[arg(short, long)]
+    size: bool,
+
+    /// Show the size of everything below directories instead of their own size
+    #[arg(long)]
+    total_size: bool,
+
+    /// Add the size of the listed files to the summary below the tree
+    #[arg(long)]
+    summary_bytes: bool,
+
+    /// Show and sort by WORD instead of the modification time
+    #[arg(long, value_enum, value_name = "WORD", default_value_t = TimeKind::Mtime)]
+    time: TimeKind,
+
+    /// Format of timestamps: default, iso, long-iso, full-iso, relative or +FORMAT
+    #[arg(
+        long,
+        value_name = "STYLE",
+        value_parser = time::parse_time_style,
+        default_value = "default"
+    )]
+    time_style: TimeStyle,
+
+    /// Sort by WORD instead of name
+    #[arg(long, value_enum, value_name = "WORD", default_value_t = SortKey::Name)]
+    sort: SortKey,
+
+    /// Sort by file size, largest first
+    #[arg(short = 'S')]
+    size_sort: bool,
+
+    /// Sort by time, newest first
+    #[arg(short = 'T')]
+    time_sort: bool,
+
+    /// Reverse the order while sorting
+    #[arg(short, long)]
+    reverse: bool,
+
+    /// Group directories before files
+    #[arg(long)]
+    group_directories_first: bool,
+
+    /// Do not descend into directories on other file systems
+    #[arg(long)]
+    one_file_system: bool,
+
+    /// Follow symbolic links and show the file they point to
+    #[arg(short = 'L', long)]
+    dereference: bool,
+
+    /// Print the directory tree as nested JSON
+    #[arg(long, conflicts_with = "ndjson")]
+    json: bool,
+
+    /// Print one JSON object per line for every entry
+    #[arg(long)]
+    ndjson: bool,
+
+    /// List subdirectories recursively
+    #[arg(short = 'R', long, conflicts_with_all = ["tree", "level"])]
+    recursive: bool,
+
+    /// Descend at most N levels, with -R, the tree and JSON
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Write names in STYLE, by default shell-escape on a terminal and literal otherwise
+    #[arg(long, value_enum, value_name = "STYLE", overrides_with = "escape")]
+    quoting_style: Option<QuotingStyle>,
+
+    /// Print C-style escapes for nongraphic characters
+    #[arg(short = 'b', long, overrides_with = "quoting_style")]
+    escape: bool,
+
+    /// Print ? instead of nongraphic characters
+    #[arg(short = 'q', long)]
+    hide_control_chars: bool,
+
+    /// Show the git status of the files in the long listing and the tree
+    #[arg(long)]
+    git: bool,
+
+    /// Color the file names using LS_COLORS
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        default_value_t = ColorWhen::Auto,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "always"
+    )]
+    color: ColorWhen,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum ColorWhen {
+    /// Color only when writing to a terminal
+    Auto,
+    /// Always color, even when NO_COLOR is set
+    Always,
+    /// Never color
+    Never,
+}
+
+impl LsArgs {
+    /// Long listing, also implied by the options that remove its columns
+    fn long(&self) -> bool {
+        self.list || self.numeric_uid_gid || self.no_group || self.no_owner
+    }
+
+    /// Show the tree view, with --tree or a level
+    fn is_tree(&self) -> bool {
+        self.tree || self.level.is_some()
+    }
+
+    /// Levels of the tree to show, a single level without the tree
+    fn tree_depth(&self) -> usize {
+        let depth = match (self.level, self.tree) {
+            (Some(level), _) => level,
+            (None, true) => usize::MAX,
+            (None, false) => 1,
+        };
+        self.max_depth
+            .map_or(depth, |max_depth| depth.min(max_depth))
+    }
+
+    /// List the hidden files, the ones starting with a dot
+    fn show_hidden(&self) -> bool {
+        self.all || self.almost_all
+    }
+
+    /// List . and .. in every directory, not in the tree or JSON where they cannot be followed
+    fn dot_entries(&self) -> bool {
+        self.all && !self.is_tree() && !self.json && !self.ndjson
+    }
+
+    /// Patterns of the entries not to list, including the backups with -B
+    fn ignore_patterns(&self) -> Vec<&str> {
+        let mut patterns: Vec<&str> = self.ignore.iter().map(String::as_str).collect();
+        if self.ignore_backups {
+            patterns.extend(["*~", ".*~"]);
+        }
+        patterns
+    }
+
+    /// Levels of directories to list below the operands
+    fn list_depth(&self) -> usize {
+        if self.directory {
+            0
+        } else if self.recursive {
+            self.max_depth.unwrap_or(usize::MAX)
+        } else if (self.json || self.ndjson) && !self.is_tree() {
+            /* JSON has the whole tree, unless the depth is limited */
+            self.max_depth.unwrap_or(usize::MAX)
+        } else {
+            self.tree_depth()
+        }
+    }
+
+    /// Levels of directories to read below the operands, all of them to add up
+    /// their sizes with --total-size or to find the directories to prune
+    fn read_depth(&self) -> usize {
+        match self.total_size || self.prune {
+            true => usize::MAX,
+            false => self.list_depth(),
+        }
+    }
+
+    /// Check if the name matches one of the --include patterns
+    fn is_included(&self, name: &str) -> bool {
+        self.include
+            .iter()
+            .any(|pattern| glob::matches(pattern, name))
+    }
+
+    /// Format of sizes, counted in blocks of `default_block` unless told otherwise
+    fn size_format(&self, default_block: u64) -> SizeFormat {
+        if self.human_readable {
+            SizeFormat::Human
+        } else if self.si {
+            SizeFormat::Si
+        } else if let Some(block_size) = &self.block_size {
+            block_size.clone()
+        } else {
+            SizeFormat::blocks(default_block)
+        }
+    }
+
+    /// Sort key, where -S and -T take precedence over --sort
+    fn sort_key(&self) -> SortKey {
+        if self.size_sort {
+            SortKey::Size
+        } else if self.time_sort {
+            SortKey::Time
+        } else {
+            self.sort
+        }
+    }
+
+    /// Quoting style of the names, from the options, QUOTING_STYLE or the output
+    fn quoting_style(&self) -> QuotingStyle {
+        if self.escape {
+            QuotingStyle::Escape
+        } else if let Some(style) = self.quoting_style {
+            style
+        } else if let Some(style) = std::env::var("QUOTING_STYLE")
+            .ok()
+            .and_then(|s| QuotingStyle::from_str(&s, false).ok())
+        {
+            style
+        } else if std::io::stdout().is_terminal() {
+            QuotingStyle::ShellEscape
+        } else {
+            QuotingStyle::Literal
+        }
+    }
+
+    /// Layout of the short listing, columns on a terminal and one per line otherwise
+    fn format(&self) -> Format {
+        if self.one_per_line {
+            Format::OnePerLine
+        } else if self.across {
+            Format::Across
+        } else if self.columns || std::io::stdout().is_terminal() {
+            Format::Vertical
+        } else {
+            Format::OnePerLine
+        }
+    }
 }
 
 static ARGS: OnceLock<LsArgs> = OnceLock::new();
+/// Styles from LS_COLORS, None when output should not be colored
+static COLORS: OnceLock<Option<LsColors>> = OnceLock::new();
 static ITEM_SIGN: &str = "|-";
 static LAST_SIGN: &str = "|_";
 
 struct FSFile {
-    name: String,
+    name: OsString,
     path_buf: PathBuf,
-    metadata: Metadata,
+    /// None when the entry could not be accessed
+    metadata: Option<Metadata>,
     entry_type: FSFileType,
 }
 
 enum FSFileType {
     File,
     Dir(DirType),
+    Symlink(LinkType),
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    Inaccessible(InaccessibleType),
 }
 
 struct DirType {
     pub childs: Vec<FSFile>,
+    /// The contents were read and are listed, so that an unread directory can be
+    /// told apart from an empty one
+    pub read: bool,
+    /// Why the directory was not read, None when it was read or is below the depth
+    pub skipped: Option<Skipped>,
+    /// Error from reading the directory, None when it was read
+    pub error: Option<LsError>,
+    /// Size of the directory and everything below it, with --total-size
+    pub total: Option<TotalSize>,
+}
+
+/// Sizes of all files below a directory, with --total-size
+#[derive(Clone, Copy, Default, Debug)]
+struct TotalSize {
+    /// Sum of the sizes of the files
+    apparent: u64,
+    /// Sum of the space allocated for the files
+    allocated: u64,
+}
+
+impl TotalSize {
+    /// Size of a single file, nothing for an entry that could not be accessed
+    fn of(metadata: Option<&Metadata>) -> TotalSize {
+        match metadata {
+            Some(metadata) => TotalSize {
+                apparent: metadata.size(),
+                /* blocks() is always in units of 512 bytes */
+                allocated: metadata.blocks() * 512,
+            },
+            None => TotalSize::default(),
+        }
+    }
+}
+
+/// Reasons to not descend into a directory
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Skipped {
+    /// The directory is one of its own ancestors
+    Recursive,
+    /// The directory is on another file system than the operand, with --one-file-system
+    MountPoint,
+}
+
+impl Skipped {
+    fn note(&self) -> &'static str {
+        match self {
+            Skipped::Recursive => "recursive, not followed",
+            Skipped::MountPoint => "mount point, not followed",
+        }
+    }
+}
+
+struct LinkType {
+    /// Path the link points to, as stored in the link
+    pub target: PathBuf,
+    /// Metadata of the file pointed to, None when the link is broken
+    pub target_metadata: Option<Metadata>,
+}
+
+/// An entry in a directory whose metadata could not be read
+struct InaccessibleType {
+    /// Letter of the type from the directory entry, `?` when it is not known
+    pub kind: char,
+    pub error: LsError,
 }
 
 fn main() {
@@ -54,299 +475,939 @@ fn main() {
 
     trace!("Starting rsls");
 
-    // current dir or sub dir
-    let path_buf = match &args.name {
-        Some(name) => PathBuf::from(name),
-        None => env::current_dir().expect("Failed to read current dir"),
+    COLORS.get_or_init(|| {
+        let enabled = match args.color {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => std::io::stdout().is_terminal(),
+        };
+        if args.color == ColorWhen::Always {
+            /* Override NO_COLOR when explicitly asked for colors */
+            style::force_color_output(true);
+        }
+        enabled.then(LsColors::from_env)
+    });
+
+    // current dir or the given operands
+    let operands = match args.names.is_empty() {
+        true => vec![PathBuf::from(".")],
+        false => args.names.clone(),
     };
 
-    trace!(
-        "list dir: {:?}",
-        path_buf.file_name().expect("Could not read directory name")
-    );
+    /* Command line links are followed, unless the link itself is listed */
+    let follow = args.dereference || !(args.long() || args.directory);
+    let depth = args.read_depth();
+
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for path in operands.iter() {
+        trace!("list: {:?}", path);
+        let name = path.as_os_str().to_os_string();
+        match read_entry(path.clone(), name, follow, depth) {
+            Ok(fs_file) if !args.directory && matches!(fs_file.entry_type, FSFileType::Dir(_)) => {
+                dirs.push(fs_file)
+            }
+            Ok(fs_file) => files.push(fs_file),
+            Err(e) => error::report(&e, error::SERIOUS),
+        }
+    }
+
+    if args.git {
+        git::load(&operands);
+    }
 
-    if !path_buf.is_dir() {
-        error!("Not a directory");
-        std::process::exit(1);
+    for operands in [&mut files, &mut dirs] {
+        sort::sort_files(
+            operands,
+            args.sort_key(),
+            args.time,
+            args.reverse,
+            args.group_directories_first,
+        );
     }
 
-    let dir_entry = read_directory(path_buf, args.tree).expect("Failed to read dir");
+    /* Name the directories when there is more than one thing to list */
+    let headings = operands.len() > 1;
+    match list(&files, &dirs, headings) {
+        Ok(()) => {}
+        /* Stop quietly when the reader went away, like ls killed by SIGPIPE */
+        Err(e) if e.is_broken_pipe() => {}
+        Err(e) => error::report(&e, error::SERIOUS),
+    }
+    std::process::exit(error::exit_status());
+}
 
-    // TODO: Fix listing based on new type
-    list(&dir_entry).expect("Failed to print stuff");
+/// Read a single entry, reading directories until depth reaches zero
+fn read_entry(
+    path: PathBuf,
+    name: OsString,
+    follow: bool,
+    depth: usize,
+) -> Result<FSFile, LsError> {
+    let fs_file = stat_entry(path, name, follow)?;
+    match fs_file.entry_type {
+        FSFileType::Dir(_) => read_directory(fs_file, depth),
+        _ => Ok(fs_file),
+    }
 }
 
-fn read_directory(path: PathBuf, depth: u8) -> Result<FSFile, String> {
-    /* TODO: Verify it is a dir */
-    trace!("Read dir: {:?}", path);
+/// Read the metadata of an entry, without reading the contents of directories
+fn stat_entry(path: PathBuf, name: OsString, follow: bool) -> Result<FSFile, LsError> {
+    let metadata = match follow {
+        /* A broken link cannot be followed, so show the link itself */
+        true => path.metadata().or_else(|_| path.symlink_metadata()),
+        false => path.symlink_metadata(),
+    }
+    .map_err(|source| LsError::Access {
+        path: path.clone(),
+        source,
+    })?;
+
+    let entry_type = if metadata.is_dir() {
+        FSFileType::Dir(DirType {
+            childs: Vec::new(),
+            read: false,
+            skipped: None,
+            error: None,
+            total: None,
+        })
+    } else if metadata.is_file() {
+        FSFileType::File
+    } else if metadata.is_symlink() {
+        let target = path.read_link().map_err(|source| LsError::ReadLink {
+            path: path.clone(),
+            source,
+        })?;
+        let target_metadata = path.metadata().ok();
+        if target_metadata.is_none() {
+            trace!("Broken symlink: {:?}", path);
+        }
+        FSFileType::Symlink(LinkType {
+            target,
+            target_metadata,
+        })
+    } else if let Some(entry_type) = special_file_type(&metadata) {
+        /* Fifos, sockets and devices */
+        entry_type
+    } else {
+        return Err(LsError::UnknownType { path });
+    };
+
+    Ok(FSFile {
+        name,
+        path_buf: path,
+        metadata: Some(metadata),
+        entry_type,
+    })
+}
+
+/// Keep an entry of a directory that could not be accessed, with the type the
+/// directory gives for it
+fn inaccessible_entry(entry: &DirEntry, error: LsError) -> FSFile {
+    let kind = match entry.file_type() {
+        Ok(t) if t.is_dir() => 'd',
+        Ok(t) if t.is_file() => '-',
+        Ok(t) if t.is_symlink() => 'l',
+        Ok(t) if t.is_fifo() => 'p',
+        Ok(t) if t.is_socket() => 's',
+        Ok(t) if t.is_block_device() => 'b',
+        Ok(t) if t.is_char_device() => 'c',
+        _ => '?',
+    };
+    FSFile {
+        name: entry.file_name(),
+        path_buf: entry.path(),
+        metadata: None,
+        entry_type: FSFileType::Inaccessible(InaccessibleType { kind, error }),
+    }
+}
 
-    let args = ARGS.get().ok_or("Failed to read settings")?;
-    let mut dir_type = DirType { childs: Vec::new() };
-    let name = path
-        .file_name()
-        .ok_or(String::from("failed to read file name"))?
-        .to_str()
-        .ok_or(String::from("Failed to read file name"))?;
-    let metadata = path
-        .metadata()
-        .map_err(|_| String::from("Failed to open metadata"))?;
+/// A directory that is being read, with the entries that are left to read
+struct DirFrame {
+    fs_file: FSFile,
+    childs: Vec<FSFile>,
+    pending: std::vec::IntoIter<DirEntry>,
+    depth: usize,
+    /// Rules of the ignore files in the directory
+    ignore: Ignore,
+    /// The directory or one above it matches --include with --matchdirs, so that
+    /// all of its contents are listed
+    matched: bool,
+}
 
+/// Read the directory `depth` levels down, keeping the directories being read on
+/// the heap so that deep hierarchies cannot overflow the stack
+fn read_directory(fs_file: FSFile, depth: usize) -> Result<FSFile, LsError> {
+    let args = ARGS.get().ok_or(LsError::Settings)?;
     if depth == 0 {
         /* Do not read dir, just return the FSObject */
-        return Ok(FSFile {
-            name: String::from(name),
-            path_buf: path.clone(),
-            metadata,
-            entry_type: FSFileType::Dir(dir_type),
-        });
+        return Ok(fs_file);
     }
 
-    /* Get all directory entires */
-    let read_dir = path
-        .read_dir()
-        .map_err(|_| String::from("Failed to read dir"))?;
+    let root_dev = fs_file.metadata.as_ref().map(|m| m.dev());
+    let mut linked = HashSet::new();
+    let operand_ignores = Ignore::for_operand(&fs_file.path_buf, args);
+    let ignore = Ignore::for_dir(&fs_file.path_buf, args);
+    let ignores: Vec<&Ignore> = operand_ignores.iter().chain([&ignore]).collect();
+    let pending = dir_entries(&fs_file.path_buf, &ignores, false)?.into_iter();
+    let mut stack = vec![DirFrame {
+        fs_file,
+        childs: Vec::new(),
+        pending,
+        depth,
+        ignore,
+        matched: false,
+    }];
+    while let Some(frame) = stack.last_mut() {
+        if let Some(d) = frame.pending.next() {
+            let mut child = match stat_entry(d.path(), d.file_name(), args.dereference) {
+                Ok(child) => child,
+                Err(e) => {
+                    /* Keep the entry, with question marks for what could not be read */
+                    error::report(&e, error::MINOR);
+                    store_child(&mut stack, inaccessible_entry(&d, e));
+                    continue;
+                }
+            };
 
-    /* Filter out hidden files if not all argument */
-    let dir_entry = match args.all {
-        false => filter_hidden(read_dir),
-        true => read_dir.filter_map(|e| e.ok()).collect(),
-    };
+            /* Descend into directories, store everything else in current directory */
+            let child_depth = frame.depth - 1;
+            let matched = frame.matched
+                || (args.matchdirs && args.is_included(&child.name.to_string_lossy()));
+            let is_dir = matches!(child.entry_type, FSFileType::Dir(_));
+            let skipped = if !is_dir {
+                None
+            } else if args.one_file_system && child.metadata.as_ref().map(|m| m.dev()) != root_dev {
+                Some(Skipped::MountPoint)
+            } else if child_depth > 0 && is_ancestor(&stack, child.metadata.as_ref()) {
+                /* A link or bind mount back up the tree, reading it would never end */
+                Some(Skipped::Recursive)
+            } else {
+                None
+            };
 
-    for d in dir_entry {
-        if let Ok(subdir_metadata) = d.metadata() {
-            if subdir_metadata.is_dir() {
-                /* Recursivley read directory */
-                if let Ok(sub_dir) = read_directory(d.path(), depth - 1) {
-                    /* Store read directory in current directory */
-                    dir_type.childs.push(sub_dir);
+            if let Some(skipped) = skipped {
+                warn!("Not reading {:?}: {}", child.path_buf, skipped.note());
+                if let FSFileType::Dir(dir_type) = &mut child.entry_type {
+                    dir_type.skipped = Some(skipped);
                 }
-            } else if subdir_metadata.is_file() {
-                /* Store the file */
-                if let Some(subdir_name) = d.file_name().to_str() {
-                    let fs_file = FSFile {
-                        name: String::from(subdir_name),
-                        path_buf: d.path(),
-                        metadata: subdir_metadata,
-                        entry_type: FSFileType::File,
-                    };
-                    dir_type.childs.push(fs_file);
+                store_child(&mut stack, child);
+            } else if is_dir && child_depth > 0 {
+                /* The ignore files of all directories down to this one apply */
+                let ignore = Ignore::for_dir(&child.path_buf, args);
+                let ignores: Vec<&Ignore> = operand_ignores
+                    .iter()
+                    .chain(stack.iter().map(|frame| &frame.ignore))
+                    .chain([&ignore])
+                    .collect();
+                match dir_entries(&child.path_buf, &ignores, matched) {
+                    Ok(pending) => stack.push(DirFrame {
+                        fs_file: child,
+                        childs: Vec::new(),
+                        pending: pending.into_iter(),
+                        depth: child_depth,
+                        ignore,
+                        matched,
+                    }),
+                    Err(e) => {
+                        /* Keep the directory, with the error to show next to it */
+                        error::report(&e, error::MINOR);
+                        if let FSFileType::Dir(dir_type) = &mut child.entry_type {
+                            dir_type.error = Some(e);
+                        }
+                        store_child(&mut stack, child);
+                    }
                 }
-            } else if subdir_metadata.is_symlink() {
-                /* Store the symlink */
-                trace!("Symlink, should display it in better color");
+            } else {
+                store_child(&mut stack, child);
             }
-        } else {
-            warn!("Failed to open meta data of child dir.");
+            continue;
+        }
+
+        /* All entries are read, store the directory in its parent */
+        let Some(frame) = stack.pop() else {
+            break;
+        };
+        let mut fs_file = frame.fs_file;
+        let mut childs = frame.childs;
+        /* Pruned directories are left out of their parent */
+        let pruned = args.prune && childs.is_empty() && !frame.matched;
+
+        let total = args
+            .total_size
+            .then(|| total_size(fs_file.metadata.as_ref(), &childs, &mut linked));
+        let read = stack.len() < args.list_depth();
+        if !read {
+            /* Only read to add up the sizes */
+            childs.clear();
+        } else if args.dot_entries() {
+            /* The directory itself and its parent, listed but never read */
+            for dot in [".", ".."] {
+                match stat_entry(fs_file.path_buf.join(dot), OsString::from(dot), false) {
+                    Ok(dot_file) => childs.push(dot_file),
+                    Err(e) => error::report(&e, error::MINOR),
+                }
+            }
+        }
+
+        /* Sort childs */
+        sort::sort_files(
+            &mut childs,
+            args.sort_key(),
+            args.time,
+            args.reverse,
+            args.group_directories_first,
+        );
+        fs_file.entry_type = FSFileType::Dir(DirType {
+            childs,
+            read,
+            skipped: None,
+            error: None,
+            total,
+        });
+
+        match stack.last_mut() {
+            Some(_) if pruned => trace!("Prune {:?}", fs_file.path_buf),
+            Some(parent) => parent.childs.push(fs_file),
+            None => return Ok(fs_file),
         }
     }
+    unreachable!("the operand is returned when its frame is popped")
+}
 
-    /* Sort childs */
-    dir_type
-        .childs
-        .sort_by_key(|fs_file| fs_file.path_buf.clone());
-    Ok(FSFile {
-        name: String::from(name),
-        path_buf: path.clone(),
-        metadata,
-        entry_type: FSFileType::Dir(dir_type),
+/// Add up the size of the directory and everything below it, counting files with
+/// several hard links once
+fn total_size(
+    metadata: Option<&Metadata>,
+    childs: &[FSFile],
+    linked: &mut HashSet<(u64, u64)>,
+) -> TotalSize {
+    let mut total = TotalSize::of(metadata);
+    for child in childs {
+        let size = match &child.entry_type {
+            FSFileType::Dir(DirType {
+                total: Some(child_total),
+                ..
+            }) => *child_total,
+            FSFileType::Dir(_) => TotalSize::of(child.metadata.as_ref()),
+            _ => match &child.metadata {
+                Some(m) if m.nlink() > 1 && !linked.insert((m.dev(), m.ino())) => continue,
+                metadata => TotalSize::of(metadata.as_ref()),
+            },
+        };
+        total.apparent += size.apparent;
+        total.allocated += size.allocated;
+    }
+    total
+}
+
+/// Store the entry in the directory that is being read
+fn store_child(stack: &mut [DirFrame], child: FSFile) {
+    if let Some(frame) = stack.last_mut() {
+        frame.childs.push(child);
+    }
+}
+
+/// Check if the directory is the same as one of the directories being read
+fn is_ancestor(stack: &[DirFrame], metadata: Option<&Metadata>) -> bool {
+    let Some(metadata) = metadata else {
+        return false;
+    };
+    stack.iter().any(|frame| {
+        frame
+            .fs_file
+            .metadata
+            .as_ref()
+            .is_some_and(|m| m.dev() == metadata.dev() && m.ino() == metadata.ino())
     })
 }
 
-fn filter_hidden(read_dir: ReadDir) -> Vec<DirEntry> {
+/// Get the entries of a directory, without the hidden, ignored and excluded ones
+fn dir_entries(
+    path: &Path,
+    ignores: &[&Ignore],
+    include_all: bool,
+) -> Result<Vec<DirEntry>, LsError> {
+    trace!("Read dir: {:?}", path);
+    let args = ARGS.get().ok_or(LsError::Settings)?;
+
+    /* Get all directory entires */
+    let read_dir = path.read_dir().map_err(|source| LsError::OpenDir {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    /* Entries that fail to be read are reported and left out */
+    let dir_entry = read_dir
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(source) => {
+                let e = LsError::ReadDir {
+                    path: path.to_path_buf(),
+                    source,
+                };
+                error::report(&e, error::MINOR);
+                None
+            }
+        })
+        .collect();
+
+    /* Filter out hidden files if not all argument */
+    let dir_entry = match args.show_hidden() {
+        false => filter_hidden(dir_entry),
+        true => dir_entry,
+    };
+
+    /* Filter out ignored files before they are read */
+    let dir_entry = filter_patterns(dir_entry, args, include_all);
+    Ok(dir_entry
+        .into_iter()
+        .filter(|entry| {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            !ignore::is_ignored(ignores, &entry.path(), is_dir)
+        })
+        .collect())
+}
+
+/// Type of a file that is neither a regular file, a directory nor a link
+fn special_file_type(metadata: &Metadata) -> Option<FSFileType> {
+    let file_type = metadata.file_type();
+    if file_type.is_fifo() {
+        Some(FSFileType::Fifo)
+    } else if file_type.is_socket() {
+        Some(FSFileType::Socket)
+    } else if file_type.is_block_device() {
+        Some(FSFileType::BlockDevice)
+    } else if file_type.is_char_device() {
+        Some(FSFileType::CharDevice)
+    } else {
+        trace!("Unknown file type: {:?}", file_type);
+        None
+    }
+}
+
+/// Split a device number into its major and minor number, like glibc does
+fn device_numbers(rdev: u64) -> (u64, u64) {
+    let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & !0xfff);
+    let minor = (rdev & 0xff) | ((rdev >> 12) & !0xff);
+    (major, minor)
+}
+
+/// Drop the entries matching an ignore pattern, and the files not matching an
+/// include pattern unless all of them are included
+fn filter_patterns(entries: Vec<DirEntry>, args: &LsArgs, include_all: bool) -> Vec<DirEntry> {
+    let ignore = args.ignore_patterns();
+    if ignore.is_empty() && (args.include.is_empty() || include_all) {
+        return entries;
+    }
+
+    entries
+        .into_iter()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if ignore.iter().any(|pattern| glob::matches(pattern, &name)) {
+                trace!("Ignore {:?}", name);
+                return false;
+            }
+            /* Directories are kept to find the included files below them */
+            args.include.is_empty()
+                || include_all
+                || entry.file_type().is_ok_and(|t| t.is_dir())
+                || args.is_included(&name)
+        })
+        .collect()
+}
+
+fn filter_hidden(entries: Vec<DirEntry>) -> Vec<DirEntry> {
     /* For each DirEntry, if the name starts with "."
      * return None, else return the entry and collect to a Vec<DirEntry> */
-    read_dir
-        .filter_map(|res_entry| {
-            res_entry.ok().and_then(|entry| {
-                match entry.file_name().to_str().map(|s| s.starts_with('.')) {
-                    Some(false) => Some(entry),
-                    Some(true) => {
-                        trace!("Filter hidden file{:?}", entry.file_name());
-                        None
-                    }
-                    None => {
-                        error!("error filtering hidden files");
-                        None
-                    }
+    entries
+        .into_iter()
+        .filter(
+            |entry| match entry.file_name().as_bytes().starts_with(b".") {
+                false => true,
+                true => {
+                    trace!("Filter hidden file{:?}", entry.file_name());
+                    false
                 }
-            })
-        })
+            },
+        )
         .collect()
 }
 
-fn list(fs_file: &FSFile) -> Result<(), String> {
+/// Print the file operands as one group, followed by the contents of each directory
+fn list(files: &[FSFile], dirs: &[FSFile], headings: bool) -> Result<(), LsError> {
     let mut stdout = std::io::stdout();
-    let args = ARGS.get().unwrap();
-    if args.list && args.tree > 1 {
-        warn!("Cannot display list and tree, will do list");
-        println!("Mode\t\t user\t group\t size\t\t name"); // TODO: Convert to print
-        print_dir(&mut stdout, fs_file)?;
-        queue!(stdout, style::Print("\n")).map_err(|_| String::from("Failed to print name"))?;
-        let _ = stdout.flush();
+    let args = ARGS.get().ok_or(LsError::Settings)?;
+    if args.json || args.ndjson {
+        if args.json {
+            json::print_json(&mut stdout, files, dirs)?;
+        } else {
+            json::print_ndjson(&mut stdout, files, dirs)?;
+        }
+        stdout.flush()?;
         return Ok(());
     }
+    /* Names are written with their bytes, which JSON cannot hold */
+    let mut stdout = quoting::RawBytes(stdout);
 
-    if args.list {
-        println!("Mode\t\t user\t group\t size\t\t name"); // TODO: Convert to print
+    let mut summary = Summary::default();
+    let mut first = true;
+    if !files.is_empty() {
+        print_dir(&mut stdout, files)?;
+        files.iter().for_each(|file| summary.add(file));
+        first = false;
     }
 
-    if args.tree > 1 {
-        print_dir_rec(&mut stdout, fs_file, 0)?;
-    } else {
-        print_dir(&mut stdout, fs_file)?;
+    /* Directories still to print, with -R subdirectories are added as they are reached */
+    let mut stack: Vec<(&FSFile, usize)> = dirs.iter().rev().map(|dir| (dir, 1)).collect();
+    while let Some((dir, level)) = stack.pop() {
+        if !first {
+            queue!(stdout, style::Print("\n"))?;
+        }
+        first = false;
+        if headings || args.recursive {
+            queue!(
+                stdout,
+                style::Print(format!("{}:\n", quoting::quote(dir.path_buf.as_os_str())))
+            )?;
+        }
+
+        if args.is_tree() {
+            print_dir_rec(&mut stdout, dir, 0, &mut summary)?;
+        } else if let FSFileType::Dir(dir_type) = &dir.entry_type {
+            /* The error was reported while reading, there are no contents to show */
+            if dir_type.error.is_none() {
+                if args.long() {
+                    print_total(&mut stdout, &dir_type.childs)?;
+                }
+                print_dir(&mut stdout, &dir_type.childs)?;
+            }
+            /* Only the directories within the depth limit have been read */
+            if args.recursive && level < args.list_depth() {
+                stack.extend(
+                    dir_type
+                        .childs
+                        .iter()
+                        .rev()
+                        .filter(|child| child.name != "." && child.name != "..")
+                        .filter(|child| match &child.entry_type {
+                            FSFileType::Dir(dir_type) => match dir_type.skipped {
+                                Some(Skipped::Recursive) => {
+                                    let e = LsError::AlreadyListed {
+                                        path: child.path_buf.clone(),
+                                    };
+                                    error::report(&e, error::SERIOUS);
+                                    false
+                                }
+                                Some(Skipped::MountPoint) => false,
+                                None => true,
+                            },
+                            _ => false,
+                        })
+                        .map(|child| (child, level + 1)),
+                );
+            }
+        }
+    }
+
+    /* Like tree, count what was listed below all the trees */
+    if args.is_tree() {
+        queue!(stdout, style::Print(format!("\n{}\n", summary.line())))?;
     }
 
-    queue!(stdout, style::Print("\n")).map_err(|_| String::from("Failed to print name"))?;
-    let _ = stdout.flush();
+    stdout.flush()?;
     Ok(())
 }
 
-/// Prints a single directory
-fn print_dir<W>(w: &mut W, fs_file: &FSFile) -> Result<(), String>
-where
-    W: std::io::Write,
-{
-    let args = ARGS.get().unwrap();
-    match &fs_file.entry_type {
-        FSFileType::Dir(dir_type) => {
-            for child in dir_type.childs.iter() {
-                let output_string = if args.list {
-                    parse_dir_entry(child)?
-                } else {
-                    let mut n = String::with_capacity(64);
-                    n.push_str(child.name.as_str());
-                    n.push('\t');
-                    n
-                };
-                queue!(w, style::Print(output_string.to_string()))
-                    .map_err(|_| String::from("Failed to print name"))?;
+/// Counts of the entries shown in the tree
+#[derive(Default)]
+struct Summary {
+    dirs: usize,
+    files: usize,
+    /// Sum of the sizes of the files
+    bytes: u64,
+}
+
+impl Summary {
+    fn add(&mut self, fs_file: &FSFile) {
+        match &fs_file.entry_type {
+            /* Directories that could not be accessed are still directories */
+            FSFileType::Dir(_) | FSFileType::Inaccessible(InaccessibleType { kind: 'd', .. }) => {
+                self.dirs += 1
+            }
+            _ => {
+                self.files += 1;
+                self.bytes += TotalSize::of(fs_file.metadata.as_ref()).apparent;
             }
         }
-        FSFileType::File => {
-            error!("Cannot list file");
+    }
+
+    /// `2 directories, 1 file`, followed by the size with --summary-bytes
+    fn line(&self) -> String {
+        let args = ARGS.get().unwrap();
+        let plural = |count: usize, one: &str, many: &str| match count {
+            1 => format!("{} {}", count, one),
+            _ => format!("{} {}", count, many),
+        };
+        let mut line = format!(
+            "{}, {}",
+            plural(self.dirs, "directory", "directories"),
+            plural(self.files, "file", "files")
+        );
+        if args.summary_bytes {
+            let format = args.size_format(1);
+            let size = size::format_size(self.bytes, &format);
+            /* Plain numbers are bytes, the other formats carry their unit */
+            match format == SizeFormat::blocks(1) {
+                true => line.push_str(&format!(", {} bytes", size)),
+                false => line.push_str(&format!(", {}", size)),
+            }
         }
+        line
+    }
+}
+
+/// Print the allocated size of the entries of a directory, like the `total` of GNU ls
+fn print_total<W>(w: &mut W, childs: &[FSFile]) -> Result<(), LsError>
+where
+    W: std::io::Write,
+{
+    let args = ARGS.get().ok_or(LsError::Settings)?;
+    /* The entries themselves, not everything below them with --total-size */
+    let allocated: u64 = childs
+        .iter()
+        .map(|child| TotalSize::of(child.metadata.as_ref()).allocated)
+        .sum();
+    let total = size::format_size(allocated, &args.size_format(1024));
+    queue!(w, style::Print(format!("total {}\n", total)))?;
+    Ok(())
+}
+
+/// Prints a list of files, the contents of a single directory or the file operands
+fn print_dir<W>(w: &mut W, childs: &[FSFile]) -> Result<(), LsError>
+where
+    W: std::io::Write,
+{
+    let args = ARGS.get().ok_or(LsError::Settings)?;
+    if args.long() {
+        let columns = long::columns();
+        let rows = childs
+            .iter()
+            .map(|child| {
+                let mut cells = parse_dir_entry(child, &columns)?;
+                if let Some(i) = columns.iter().position(|c| *c == Column::Name) {
+                    cells[i].append(&entry_note(child));
+                }
+                Ok(cells)
+            })
+            .collect::<Result<Vec<_>, LsError>>()?;
+        long::print_table(w, &columns, &rows)?;
+    } else {
+        let cells: Vec<Cell> = childs
+            .iter()
+            .map(|child| {
+                let name = quoting::quote(&child.name);
+                Cell::new(paint_name(child, &name), &name)
+            })
+            .collect();
+        layout::print_cells(w, &cells, args.format(), layout::terminal_width())?;
     }
     Ok(())
 }
 
-/// NOTE! Incompatible with -l command due to visualization problems
-fn print_dir_rec<W>(w: &mut W, fs_file: &FSFile, depth: u8) -> Result<(), String>
+/// Prints the directory as a tree, with the long listing columns when listing
+fn print_dir_rec<W>(
+    w: &mut W,
+    fs_file: &FSFile,
+    depth: usize,
+    summary: &mut Summary,
+) -> Result<(), LsError>
 where
     W: std::io::Write,
 {
-    let args = ARGS.get().ok_or("Failet to get settings")?;
-    if args.tree <= depth {
-        return Ok(());
+    let args = ARGS.get().ok_or(LsError::Settings)?;
+    let entries = tree_entries(fs_file, depth);
+    entries.iter().for_each(|(_, child)| summary.add(child));
+
+    if args.long() {
+        let columns = long::columns();
+        let rows = entries
+            .iter()
+            .map(|(prefix, child)| {
+                let mut cells = parse_dir_entry(child, &columns)?;
+                /* Draw the tree in the name column */
+                if let Some(i) = columns.iter().position(|c| *c == Column::Name) {
+                    cells[i].prepend(prefix);
+                    cells[i].append(&entry_note(child));
+                }
+                Ok(cells)
+            })
+            .collect::<Result<Vec<_>, LsError>>()?;
+        return long::print_table(w, &columns, &rows);
+    }
+
+    for (prefix, child) in entries {
+        /* The git status goes in front of the tree, where it lines up */
+        let status = match args.git {
+            true => format!("{} ", git_cell(child).text),
+            false => String::new(),
+        };
+        queue!(
+            w,
+            style::Print(format!(
+                "{}{}{}{}\n",
+                status,
+                prefix,
+                name_cell(child).text,
+                entry_note(child)
+            ))
+        )?;
     }
-    let indent = (0..depth).map(|_| "|  ").collect::<String>();
+    Ok(())
+}
+
+/// Note shown after the name of entries that could not be accessed, and of
+/// directories that were not read or could not be read
+fn entry_note(fs_file: &FSFile) -> String {
     match &fs_file.entry_type {
-        FSFileType::Dir(dir_type) => {
-            let mut it = dir_type.childs.iter().peekable();
-            while let Some(child) = it.next() {
-                let last = it.peek().is_none();
-                match &child.entry_type {
-                    FSFileType::File => {
-                        // TODO: refactor to its own function
-                        let mut prefix = indent.clone();
-                        if last {
-                            prefix.push_str(LAST_SIGN);
-                        } else {
-                            prefix.push_str(ITEM_SIGN);
-                        };
-
-                        queue!(
-                            w,
-                            style::Print(format!("{}{}\n", prefix.clone(), child.name.clone()))
-                        )
-                        .map_err(|_| String::from("Failed to print name"))?;
-                    }
-                    FSFileType::Dir(c) => {
-                        // TODO: refactor to its own function
-                        let mut prefix = indent.clone();
-                        if last && c.childs.is_empty() {
-                            prefix.push_str(LAST_SIGN);
-                        } else {
-                            prefix.push_str(ITEM_SIGN);
-                        };
-                        queue!(
-                            w,
-                            style::Print(format!("{}{}\n", prefix.clone(), child.name.clone()))
-                        )
-                        .map_err(|_| String::from("Failed to print name"))?;
-                        let _ = print_dir_rec(w, child, depth + 1);
-                    }
+        FSFileType::Inaccessible(inaccessible) => format!("  [{}]", inaccessible.error.note()),
+        FSFileType::Dir(DirType {
+            skipped: Some(skipped),
+            ..
+        }) => format!("  [{}]", skipped.note()),
+        FSFileType::Dir(DirType {
+            error: Some(error), ..
+        }) => format!("  [{}]", error.note()),
+        _ => String::new(),
+    }
+}
+
+/// Flatten the tree into the entries to print, each with the tree drawn before its name
+fn tree_entries(fs_file: &FSFile, depth: usize) -> Vec<(String, &FSFile)> {
+    let args = ARGS.get().unwrap();
+    let max_depth = args.tree_depth();
+    let mut entries = Vec::new();
+
+    let FSFileType::Dir(dir_type) = &fs_file.entry_type else {
+        error!("Cannot list non dir type");
+        return entries;
+    };
+    if max_depth <= depth {
+        return entries;
+    }
+
+    /* Walk the tree with a stack of the directories being printed */
+    let mut stack = vec![(dir_type.childs.iter().peekable(), depth)];
+    while let Some((it, depth)) = stack.last_mut() {
+        let depth = *depth;
+        let Some(child) = it.next() else {
+            stack.pop();
+            continue;
+        };
+        let last = it.peek().is_none();
+        let mut prefix = "|  ".repeat(depth);
+        match &child.entry_type {
+            FSFileType::Dir(c) => {
+                if last && c.childs.is_empty() {
+                    prefix.push_str(LAST_SIGN);
+                } else {
+                    prefix.push_str(ITEM_SIGN);
+                };
+                entries.push((prefix, child));
+                if depth + 1 < max_depth {
+                    stack.push((c.childs.iter().peekable(), depth + 1));
                 }
             }
-        }
-        _ => {
-            error!("Cannot list non dir type");
+            _ => {
+                if last {
+                    prefix.push_str(LAST_SIGN);
+                } else {
+                    prefix.push_str(ITEM_SIGN);
+                };
+                entries.push((prefix, child));
+            }
         }
     }
-    Ok(())
+    entries
 }
 
-fn parse_dir_entry(fs_file: &FSFile) -> Result<String, String> {
-    let metadata = fs_file.metadata.clone();
-
-    /* Get name of file */
- // Can fail due to permissions, symbolic link or path errors.
-    let name = fs_file.name.clone();
+/// Create the cells of the long listing for a single entry
+fn parse_dir_entry(fs_file: &FSFile, columns: &[Column]) -> Result<Vec<Cell>, LsError> {
+    let args = ARGS.get().ok_or(LsError::Settings)?;
 
     /* Get permission of file */
-    let d = if metadata.is_dir() { "d" } else { "-" };
-    let mode = metadata.mode();
-
-    /* A bit ugly, but converting permission to letter */
-    let ue = if mode & 0o100 > 0 { "x" } else { "-" };
-    let ur = if mode & 0o200 > 0 { "r" } else { "-" };
-    let uw = if mode & 0o400 > 0 { "w" } else { "-" };
-
-    let ge = if mode & 0o010 > 0 { "x" } else { "-" };
-    let gr = if mode & 0o020 > 0 { "r" } else { "-" };
-    let gw = if mode & 0o040 > 0 { "w" } else { "-" };
-
-    let ae = if mode & 0o001 > 0 { "x" } else { "-" };
-    let ar = if mode & 0o002 > 0 { "r" } else { "-" };
-    let aw = if mode & 0o004 > 0 { "w" } else { "-" };
-
-    let mut modes = String::with_capacity(64);
-    modes.push_str(d);
-    modes.push_str(ue);
-    modes.push_str(ur);
-    modes.push_str(uw);
-    modes.push_str(ge);
-    modes.push_str(gr);
-    modes.push_str(gw);
-    modes.push_str(ae);
-    modes.push_str(ar);
-    modes.push_str(aw);
+    let d = match &fs_file.entry_type {
+        FSFileType::File => '-',
+        FSFileType::Dir(_) => 'd',
+        FSFileType::Symlink(_) => 'l',
+        FSFileType::Fifo => 'p',
+        FSFileType::Socket => 's',
+        FSFileType::BlockDevice => 'b',
+        FSFileType::CharDevice => 'c',
+        FSFileType::Inaccessible(inaccessible) => inaccessible.kind,
+    };
+
+    /* Nothing but the name and the type is known, like GNU ls show `?` for the rest */
+    let Some(metadata) = &fs_file.metadata else {
+        let unknown = |text: String| Cell::new(text.clone(), text.as_str());
+        let cells = columns
+            .iter()
+            .map(|column| match column {
+                Column::Mode => unknown(format!("{}?????????", d)),
+                Column::Git => git_cell(fs_file),
+                Column::Name => name_cell(fs_file),
+                _ => unknown(String::from("?")),
+            })
+            .collect();
+        return Ok(cells);
+    };
+    let follow = !metadata.is_symlink();
+    let modes = permissions::format_permissions(d, metadata.mode(), &fs_file.path_buf, follow);
+    let octal = permissions::format_octal(metadata.mode());
 
     /* Get user and group of file */
-    let usr = metadata.uid();
-    let grp = metadata.gid();
+    let usr = owners::user_name(metadata.uid());
+    let grp = owners::group_name(metadata.gid());
+
+    let sizes = sizes_of(fs_file);
+
+    /* Get size of file, or the device numbers of a device */
+    let size = match fs_file.entry_type {
+        FSFileType::BlockDevice | FSFileType::CharDevice => {
+            let (major, minor) = device_numbers(metadata.rdev());
+            format!("{}, {}", major, minor)
+        }
+        _ => size::format_size(sizes.apparent, &args.size_format(1)),
+    };
+
+    /* Allocated size */
+    let blocks = size::format_size(sizes.allocated, &args.size_format(1024));
+
+    /* Get the time of the file */
+    let time = time::format_time(time::timestamp(metadata, args.time), &args.time_style);
+
+    let cells = columns
+        .iter()
+        .map(|column| match column {
+            Column::Blocks => Cell::new(blocks.clone(), blocks.as_str()),
+            Column::Octal => Cell::new(octal.clone(), octal.as_str()),
+            Column::Mode => Cell::new(modes.clone(), modes.as_str()),
+            Column::User => Cell::new(usr.clone(), usr.as_str()),
+            Column::Group => Cell::new(grp.clone(), grp.as_str()),
+            Column::Size => Cell::new(size.clone(), size.as_str()),
+            Column::Time => Cell::new(time.clone(), time.as_str()),
+            Column::Git => git_cell(fs_file),
+            Column::Name => name_cell(fs_file),
+        })
+        .collect();
+
+    Ok(cells)
+}
+
+/// Size of the file, where directories have the size of everything below them with
+/// --total-size
+fn sizes_of(fs_file: &FSFile) -> TotalSize {
+    match &fs_file.entry_type {
+        FSFileType::Dir(DirType {
+            total: Some(total), ..
+        }) => *total,
+        _ => TotalSize::of(fs_file.metadata.as_ref()),
+    }
+}
+
+/// Styled name of the file, followed by the target for symbolic links
+fn name_cell(fs_file: &FSFile) -> Cell {
+    let name = quoting::quote(&fs_file.name);
+    let FSFileType::Symlink(link) = &fs_file.entry_type else {
+        return Cell::new(paint_name(fs_file, &name), &name);
+    };
+
+    let target = quoting::quote(link.target.as_os_str());
+    let style = COLORS
+        .get()
+        .and_then(|colors| colors.as_ref())
+        .and_then(|colors| colors.style_for_target(link));
+    let painted_target = match style {
+        Some(style) => style.apply(target.as_str()).to_string(),
+        None => target.clone(),
+    };
+
+    Cell::new(
+        format!("{} -> {}", paint_name(fs_file, &name), painted_target),
+        format!("{} -> {}", name, target).as_str(),
+    )
+}
 
-    /* Convert uid and gid to name */
-    let cache = UsersCache::new();
+/// Git status of the file, colored when the names are
+fn git_cell(fs_file: &FSFile) -> Cell {
+    let colored = COLORS.get().is_some_and(|colors| colors.is_some());
+    let status = git::status(&fs_file.path_buf);
+    Cell::new(status.painted(colored), &status.text())
+}
 
-    let usr = cache.get_user_by_uid(usr).ok_or("Owner")?;
-    let usr = usr.name().to_str().ok_or(String::from("Owner"))?;
+/// Quoted name of the file styled with the LS_COLORS of its type, or its git status
+fn paint_name(fs_file: &FSFile, name: &str) -> String {
+    let colors = COLORS.get().and_then(|colors| colors.as_ref());
+    /* With --git, changed files are colored by their status */
+    let git_style = match ARGS.get().is_some_and(|args| args.git) && colors.is_some() {
+        true => git::status(&fs_file.path_buf).name_style(),
+        false => None,
+    };
+    let style = git_style.or_else(|| colors.and_then(|colors| colors.style_for(fs_file)));
+    match style {
+        Some(style) => style.apply(name).to_string(),
+        None => name.to_string(),
+    }
+}
 
-    let grp = cache.get_user_by_uid(grp).ok_or("Group")?;
-    let grp = grp.name().to_str().ok_or(String::from("group"))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    /* Get size of file */
-    let size = metadata.size();
+    fn entry(name: &str, entry_type: FSFileType) -> FSFile {
+        FSFile {
+            name: OsString::from(name),
+            path_buf: PathBuf::from(name),
+            metadata: None,
+            entry_type,
+        }
+    }
 
-    modes.push('\t');
-    modes.push_str(usr);
-    modes.push('\t');
-    modes.push_str(grp);
-    modes.push('\t');
-    modes.push_str(size.to_string().as_str());
-    modes.push('\t');
-    modes.push('\t');
-    modes.push_str(name.as_str());
-    modes.push('\n');
+    fn inaccessible(name: &str, kind: char) -> FSFile {
+        let error = LsError::UnknownType {
+            path: PathBuf::from(name),
+        };
+        entry(
+            name,
+            FSFileType::Inaccessible(InaccessibleType { kind, error }),
+        )
+    }
 
-    Ok(modes)
+    #[test]
+    fn summary_counts_inaccessible_entries_by_kind() {
+        let dir = entry(
+            "dir",
+            FSFileType::Dir(DirType {
+                childs: Vec::new(),
+                read: true,
+                skipped: None,
+                error: None,
+                total: None,
+            }),
+        );
+        let mut summary = Summary::default();
+        for fs_file in [
+            dir,
+            inaccessible("sub", 'd'),
+            inaccessible("file", '-'),
+            inaccessible("unknown", '?'),
+        ] {
+            summary.add(&fs_file);
+        }
+        assert_eq!(summary.dirs, 2);
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.bytes, 0);
+    }
 }
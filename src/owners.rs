@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::ARGS;
+
+/// Names of users and groups, looked up once per id for the whole listing
+static OWNERS: OnceLock<Owners> = OnceLock::new();
+
+struct Owners {
+    users: Mutex<HashMap<u32, Option<String>>>,
+    groups: Mutex<HashMap<u32, Option<String>>>,
+}
+
+fn owners() -> &'static Owners {
+    OWNERS.get_or_init(|| Owners {
+        users: Mutex::new(HashMap::new()),
+        groups: Mutex::new(HashMap::new()),
+    })
+}
+
+/// Name of the user, None when the uid is not in the user database
+pub fn lookup_user(uid: u32) -> Option<String> {
+    let mut users = owners().users.lock().unwrap();
+    users
+        .entry(uid)
+        .or_insert_with(|| {
+            users::get_user_by_uid(uid).and_then(|u| u.name().to_str().map(String::from))
+        })
+        .clone()
+}
+
+/// Name of the group, None when the gid is not in the group database
+pub fn lookup_group(gid: u32) -> Option<String> {
+    let mut groups = owners().groups.lock().unwrap();
+    groups
+        .entry(gid)
+        .or_insert_with(|| {
+            users::get_group_by_gid(gid).and_then(|g| g.name().to_str().map(String::from))
+        })
+        .clone()
+}
+
+/// Name of the user to show, the numeric id for unknown users or with `-n`
+pub fn user_name(uid: u32) -> String {
+    let numeric = ARGS.get().is_some_and(|args| args.numeric_uid_gid);
+    match numeric {
+        true => uid.to_string(),
+        false => lookup_user(uid).unwrap_or_else(|| uid.to_string()),
+    }
+}
+
+/// Name of the group to show, the numeric id for unknown groups or with `-n`
+pub fn group_name(gid: u32) -> String {
+    let numeric = ARGS.get().is_some_and(|args| args.numeric_uid_gid);
+    match numeric {
+        true => gid.to_string(),
+        false => lookup_group(gid).unwrap_or_else(|| gid.to_string()),
+    }
+}
@@ -0,0 +1,109 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Extended attribute holding the access ACL of a file
+static ACL_ACCESS: &str = "system.posix_acl_access";
+/// Extended attribute holding the default ACL of a directory
+static ACL_DEFAULT: &str = "system.posix_acl_default";
+/// Extended attribute holding the SELinux security context
+static SELINUX: &str = "security.selinux";
+
+/// Render the mode as `drwxr-xr-x`, with setuid, setgid and sticky bits and the
+/// `+` and `.` markers for ACLs and SELinux contexts
+pub fn format_permissions(type_char: char, mode: u32, path: &Path, follow: bool) -> String {
+    let mut perms = format_mode(type_char, mode);
+    if has_xattr(path, ACL_ACCESS, follow) || has_xattr(path, ACL_DEFAULT, follow) {
+        perms.push('+');
+    } else if has_xattr(path, SELINUX, follow) {
+        perms.push('.');
+    }
+    perms
+}
+
+/// Render the type and the permission bits as `drwxr-xr-x`
+fn format_mode(type_char: char, mode: u32) -> String {
+    let mut perms = String::with_capacity(11);
+    perms.push(type_char);
+
+    /* Owner, group and others, each with the special bit shown in its x slot */
+    let classes = [
+        (0o400, 0o200, 0o100, 0o4000, 's'),
+        (0o040, 0o020, 0o010, 0o2000, 's'),
+        (0o004, 0o002, 0o001, 0o1000, 't'),
+    ];
+    for (read, write, exec, special, special_char) in classes {
+        perms.push(if mode & read != 0 { 'r' } else { '-' });
+        perms.push(if mode & write != 0 { 'w' } else { '-' });
+        perms.push(match (mode & exec != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    perms
+}
+
+/// Permission bits including the special bits in octal, e.g. `0755`
+pub fn format_octal(mode: u32) -> String {
+    format!("{:04o}", mode & 0o7777)
+}
+
+/// Check if the file has the extended attribute, without reading its value
+fn has_xattr(path: &Path, name: &str, follow: bool) -> bool {
+    let (Ok(c_path), Ok(c_name)) = (
+        CString::new(path.as_os_str().as_bytes()),
+        CString::new(name),
+    ) else {
+        return false;
+    };
+
+    // SAFETY: both strings are nul terminated and a null buffer of size 0 only
+    // queries the size of the value
+    let size = unsafe {
+        if follow {
+            libc::getxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0)
+        } else {
+            libc::lgetxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0)
+        }
+    };
+    size > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_bits() {
+        assert_eq!(format_mode('-', 0o644), "-rw-r--r--");
+        assert_eq!(format_mode('d', 0o755), "drwxr-xr-x");
+        assert_eq!(format_mode('-', 0o000), "----------");
+        assert_eq!(format_mode('l', 0o777), "lrwxrwxrwx");
+    }
+
+    #[test]
+    fn setuid() {
+        assert_eq!(format_mode('-', 0o4755), "-rwsr-xr-x");
+        assert_eq!(format_mode('-', 0o4644), "-rwSr--r--");
+    }
+
+    #[test]
+    fn setgid() {
+        assert_eq!(format_mode('-', 0o2755), "-rwxr-sr-x");
+        assert_eq!(format_mode('-', 0o2644), "-rw-r-Sr--");
+    }
+
+    #[test]
+    fn sticky() {
+        assert_eq!(format_mode('d', 0o1777), "drwxrwxrwt");
+        assert_eq!(format_mode('d', 0o1776), "drwxrwxrwT");
+    }
+
+    #[test]
+    fn octal() {
+        assert_eq!(format_octal(0o100644), "0644");
+        assert_eq!(format_octal(0o41777), "1777");
+    }
+}
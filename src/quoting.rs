@@ -0,0 +1,302 @@
+use clap::ValueEnum;
+use std::ffi::OsStr;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+
+use crate::ARGS;
+
+/// Characters that have a meaning to the shell anywhere in a word
+static SHELL_SPECIAL: &str = " \t\n!\"$&'()*;<>?[\\]^`{|}";
+/// Characters that have a meaning to the shell at the start of a word
+static SHELL_SPECIAL_START: &str = "#~";
+/// Characters that are special within double quotes
+static DOUBLE_QUOTE_SPECIAL: &str = "\"$`\\!";
+/// Bytes of names that are not valid UTF-8 are carried through the output as the
+/// private use characters from here on, and written as the bytes themselves
+static RAW_BYTE_BASE: u32 = 0x10ff00;
+
+/// How file names are written, so that any name can be shown safely
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+pub enum QuotingStyle {
+    /// Names as they are
+    Literal,
+    /// Quote names for the shell when they contain special characters
+    Shell,
+    /// Like shell, with $'' escapes for nongraphic characters
+    ShellEscape,
+    /// Quote names like C strings
+    C,
+    /// Like c, without the quotes
+    Escape,
+}
+
+/// A piece of a file name, which is not required to be valid UTF-8
+#[derive(Clone, Copy)]
+enum Unit {
+    Char(char),
+    Byte(u8),
+}
+
+impl Unit {
+    /// Graphic characters and spaces, which show up as themselves on a terminal
+    fn printable(&self) -> bool {
+        match self {
+            Unit::Char(c) => !c.is_control(),
+            Unit::Byte(_) => false,
+        }
+    }
+}
+
+/// Write the name in the quoting style of the listing
+pub fn quote(name: &OsStr) -> String {
+    let args = ARGS.get().unwrap();
+    quote_with(name, args.quoting_style(), args.hide_control_chars)
+}
+
+/// Write the name in the style, with `?` for nongraphic characters when hiding them
+fn quote_with(name: &OsStr, style: QuotingStyle, hide_control: bool) -> String {
+    let units: Vec<Unit> = units(name)
+        .into_iter()
+        .map(|unit| match hide_control && !unit.printable() {
+            true => Unit::Char('?'),
+            false => unit,
+        })
+        .collect();
+
+    let valid = units.iter().all(|unit| matches!(unit, Unit::Char(_)));
+    match style {
+        QuotingStyle::Literal => units.iter().map(raw).collect(),
+        QuotingStyle::Shell if valid => shell_quote(&units.iter().map(raw).collect::<String>()),
+        QuotingStyle::ShellEscape if units.iter().all(Unit::printable) => {
+            shell_quote(&units.iter().map(raw).collect::<String>())
+        }
+        /* Bytes that are not text can only be written as $'' escapes */
+        QuotingStyle::Shell | QuotingStyle::ShellEscape => shell_escape(&units),
+        QuotingStyle::C => {
+            let mut quoted = String::from("\"");
+            for unit in units.iter() {
+                match unit {
+                    Unit::Char('"') => quoted.push_str("\\\""),
+                    unit => escape(*unit, &mut quoted),
+                }
+            }
+            quoted.push('"');
+            quoted
+        }
+        QuotingStyle::Escape => {
+            let mut quoted = String::new();
+            for unit in units.iter() {
+                match unit {
+                    Unit::Char(' ') => quoted.push_str("\\ "),
+                    unit => escape(*unit, &mut quoted),
+                }
+            }
+            quoted
+        }
+    }
+}
+
+/// Split the name into characters and the bytes that are not valid UTF-8
+fn units(name: &OsStr) -> Vec<Unit> {
+    let mut units = Vec::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c as u32 >= RAW_BYTE_BASE {
+                /* Characters that stand for raw bytes are kept apart as their bytes */
+                let mut buf = [0; 4];
+                units.extend(c.encode_utf8(&mut buf).bytes().map(Unit::Byte));
+            } else {
+                units.push(Unit::Char(c));
+            }
+        }
+        units.extend(chunk.invalid().iter().map(|b| Unit::Byte(*b)));
+    }
+    units
+}
+
+/// The character to write, where bytes become the characters that stand for them
+fn raw(unit: &Unit) -> char {
+    match unit {
+        Unit::Char(c) => *c,
+        Unit::Byte(b) => char::from_u32(RAW_BYTE_BASE + *b as u32).unwrap(),
+    }
+}
+
+/// Writer that turns the characters standing for raw bytes back into the bytes
+pub struct RawBytes<W: Write>(pub W);
+
+impl<W: Write> Write for RawBytes<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        /* U+10FF00 to U+10FFFF are encoded as F4 8F BC-BF 80-BF */
+        let mut start = 0;
+        let mut i = 0;
+        while i + 4 <= buf.len() {
+            match buf[i..i + 4] {
+                [0xf4, 0x8f, high @ 0xbc..=0xbf, low @ 0x80..=0xbf] => {
+                    self.0.write_all(&buf[start..i])?;
+                    self.0.write_all(&[((high - 0xbc) << 6) | (low - 0x80)])?;
+                    i += 4;
+                    start = i;
+                }
+                _ => i += 1,
+            }
+        }
+        self.0.write_all(&buf[start..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Quote the text for the shell when it contains characters special to it
+fn shell_quote(text: &str) -> String {
+    let needs_quotes = text.is_empty()
+        || text
+            .chars()
+            .any(|c| SHELL_SPECIAL.contains(c) || c.is_control())
+        || text.starts_with(|c| SHELL_SPECIAL_START.contains(c));
+    if !needs_quotes {
+        text.to_string()
+    } else if text.contains('\'') && !text.contains(|c| DOUBLE_QUOTE_SPECIAL.contains(c)) {
+        /* "it's" reads better than 'it'\''s' */
+        format!("\"{}\"", text)
+    } else {
+        single_quote(text)
+    }
+}
+
+/// Quote the printable parts of the name and write the rest as $'' escapes,
+/// e.g. `'a'$'\n''b'`
+fn shell_escape(units: &[Unit]) -> String {
+    let mut quoted = String::new();
+    for run in units.chunk_by(|a, b| a.printable() == b.printable()) {
+        if run[0].printable() {
+            quoted.push_str(&single_quote(&run.iter().map(raw).collect::<String>()));
+        } else {
+            quoted.push_str("$'");
+            for unit in run {
+                escape(*unit, &mut quoted);
+            }
+            quoted.push('\'');
+        }
+    }
+    quoted
+}
+
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Write the unit with a backslash escape when it is not printable
+fn escape(unit: Unit, out: &mut String) {
+    match unit {
+        Unit::Char('\\') => out.push_str("\\\\"),
+        Unit::Char('\x07') => out.push_str("\\a"),
+        Unit::Char('\x08') => out.push_str("\\b"),
+        Unit::Char('\t') => out.push_str("\\t"),
+        Unit::Char('\n') => out.push_str("\\n"),
+        Unit::Char('\x0b') => out.push_str("\\v"),
+        Unit::Char('\x0c') => out.push_str("\\f"),
+        Unit::Char('\r') => out.push_str("\\r"),
+        Unit::Char(c) if unit.printable() => out.push(c),
+        /* Other control characters byte by byte in octal */
+        Unit::Char(c) => {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("\\{:03o}", b));
+            }
+        }
+        Unit::Byte(b) => out.push_str(&format!("\\{:03o}", b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    fn quoted(name: &[u8], style: QuotingStyle) -> String {
+        quote_with(&OsString::from_vec(name.to_vec()), style, false)
+    }
+
+    /// The bytes the quoted name is written as
+    fn written(text: &str) -> Vec<u8> {
+        let mut out = RawBytes(Vec::new());
+        out.write_all(text.as_bytes()).unwrap();
+        out.0
+    }
+
+    #[test]
+    fn literal() {
+        assert_eq!(quoted(b"it's", QuotingStyle::Literal), "it's");
+        assert_eq!(quoted(b"a\nb", QuotingStyle::Literal), "a\nb");
+        assert_eq!(quoted(b"#x", QuotingStyle::Literal), "#x");
+        assert_eq!(
+            written(&quoted(b"\xff\xfe", QuotingStyle::Literal)),
+            b"\xff\xfe"
+        );
+    }
+
+    #[test]
+    fn shell() {
+        assert_eq!(quoted(b"plain.txt", QuotingStyle::Shell), "plain.txt");
+        assert_eq!(quoted(b"it's", QuotingStyle::Shell), "\"it's\"");
+        assert_eq!(quoted(b"it's $x", QuotingStyle::Shell), "'it'\\''s $x'");
+        assert_eq!(quoted(b"a b", QuotingStyle::Shell), "'a b'");
+        assert_eq!(quoted(b"a\nb", QuotingStyle::Shell), "'a\nb'");
+        assert_eq!(quoted(b"#x", QuotingStyle::Shell), "'#x'");
+        assert_eq!(quoted(b"~x", QuotingStyle::Shell), "'~x'");
+        assert_eq!(quoted(b"x#~", QuotingStyle::Shell), "x#~");
+        assert_eq!(quoted(b"", QuotingStyle::Shell), "''");
+        /* Bytes that are not text fall back on escapes */
+        assert_eq!(quoted(b"\xff\xfe", QuotingStyle::Shell), "$'\\377\\376'");
+    }
+
+    #[test]
+    fn shell_escape_style() {
+        assert_eq!(quoted(b"it's", QuotingStyle::ShellEscape), "\"it's\"");
+        assert_eq!(quoted(b"a\nb", QuotingStyle::ShellEscape), "'a'$'\\n''b'");
+        assert_eq!(quoted(b"#x", QuotingStyle::ShellEscape), "'#x'");
+        assert_eq!(quoted(b"~x", QuotingStyle::ShellEscape), "'~x'");
+        assert_eq!(
+            quoted(b"a\xffb", QuotingStyle::ShellEscape),
+            "'a'$'\\377''b'"
+        );
+    }
+
+    #[test]
+    fn c() {
+        assert_eq!(quoted(b"it's", QuotingStyle::C), "\"it's\"");
+        assert_eq!(quoted(b"say \"hi\"", QuotingStyle::C), "\"say \\\"hi\\\"\"");
+        assert_eq!(quoted(b"a\nb\\", QuotingStyle::C), "\"a\\nb\\\\\"");
+        assert_eq!(quoted(b"#x", QuotingStyle::C), "\"#x\"");
+        assert_eq!(quoted(b"\xff\x01", QuotingStyle::C), "\"\\377\\001\"");
+    }
+
+    #[test]
+    fn escape_style() {
+        assert_eq!(quoted(b"it's", QuotingStyle::Escape), "it's");
+        assert_eq!(quoted(b"a b\n", QuotingStyle::Escape), "a\\ b\\n");
+        assert_eq!(quoted(b"~x", QuotingStyle::Escape), "~x");
+        assert_eq!(quoted(b"\xff", QuotingStyle::Escape), "\\377");
+    }
+
+    #[test]
+    fn hide_control_chars() {
+        let name = OsString::from_vec(b"a\nb\xff".to_vec());
+        assert_eq!(quote_with(&name, QuotingStyle::Literal, true), "a?b?");
+        assert_eq!(quote_with(&name, QuotingStyle::Shell, true), "'a?b?'");
+        assert_eq!(quote_with(&name, QuotingStyle::C, true), "\"a?b?\"");
+    }
+
+    #[test]
+    fn raw_byte_characters_in_names() {
+        /* A name holding one of the characters that stand for bytes keeps its bytes */
+        let name = "x\u{10ff41}".as_bytes();
+        assert_eq!(written(&quoted(name, QuotingStyle::Literal)), name);
+        assert_eq!(written("a\u{e9}b"), "a\u{e9}b".as_bytes());
+    }
+}
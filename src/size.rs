@@ -0,0 +1,205 @@
+/// Units of the human readable sizes, after plain bytes
+static UNITS: [char; 6] = ['K', 'M', 'G', 'T', 'P', 'E'];
+
+/// How a number of bytes is printed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SizeFormat {
+    /// Powers of 1024 with a unit, `-h`
+    Human,
+    /// Powers of 1000 with a unit, `--si`
+    Si,
+    /// Number of blocks of the given size, followed by the unit it was given in
+    Blocks { size: u64, suffix: String },
+}
+
+impl SizeFormat {
+    /// Plain blocks of `size` bytes without any unit
+    pub fn blocks(size: u64) -> SizeFormat {
+        SizeFormat::Blocks {
+            size,
+            suffix: String::new(),
+        }
+    }
+}
+
+/// Parse the argument of `--block-size`, e.g. `1024`, `K`, `4KiB`, `MB` or `si`
+pub fn parse_block_size(arg: &str) -> Result<SizeFormat, String> {
+    match arg {
+        "human-readable" => return Ok(SizeFormat::Human),
+        "si" => return Ok(SizeFormat::Si),
+        _ => {}
+    }
+
+    let digits = arg.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (number, suffix) = arg.split_at(digits);
+    let number = match number {
+        "" => 1,
+        n => n
+            .parse::<u64>()
+            .map_err(|_| format!("invalid block size: {}", arg))?,
+    };
+
+    let mut chars = suffix.chars();
+    let multiplier = match chars.next() {
+        None => 1,
+        Some(unit) => {
+            let exponent = UNITS
+                .iter()
+                .position(|u| *u == unit.to_ascii_uppercase())
+                .ok_or(format!("invalid block size: {}", arg))?
+                + 1;
+            let base: u64 = match chars.as_str() {
+                "" | "iB" => 1024,
+                "B" => 1000,
+                _ => return Err(format!("invalid block size: {}", arg)),
+            };
+            base.pow(exponent as u32)
+        }
+    };
+
+    let size = number
+        .checked_mul(multiplier)
+        .filter(|size| *size > 0)
+        .ok_or(format!("invalid block size: {}", arg))?;
+
+    /* Like GNU ls, only the unit is shown after the sizes, not the number */
+    Ok(SizeFormat::Blocks {
+        size,
+        suffix: if digits == 0 {
+            suffix.to_string()
+        } else {
+            String::new()
+        },
+    })
+}
+
+/// Format a number of bytes
+pub fn format_size(bytes: u64, format: &SizeFormat) -> String {
+    match format {
+        SizeFormat::Human => human(bytes, 1024, false),
+        SizeFormat::Si => human(bytes, 1000, true),
+        SizeFormat::Blocks { size, suffix } => format!("{}{}", bytes.div_ceil(*size), suffix),
+    }
+}
+
+/// Scale the bytes to the largest unit, rounding up like GNU ls
+fn human(bytes: u64, base: u64, si: bool) -> String {
+    if bytes < base {
+        return bytes.to_string();
+    }
+
+    let base = base as f64;
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= base && unit < UNITS.len() {
+        value /= base;
+        unit += 1;
+    }
+
+    /* One decimal below ten, none above */
+    let mut text = if value < 10.0 {
+        let rounded = (value * 10.0).ceil() / 10.0;
+        if rounded < 10.0 {
+            format!("{:.1}", rounded)
+        } else {
+            String::from("10")
+        }
+    } else {
+        let rounded = value.ceil();
+        if rounded >= base && unit < UNITS.len() {
+            unit += 1;
+            String::from("1.0")
+        } else {
+            format!("{}", rounded)
+        }
+    };
+
+    let symbol = UNITS[unit - 1];
+    text.push(if si && symbol == 'K' { 'k' } else { symbol });
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(size: u64, suffix: &str) -> SizeFormat {
+        SizeFormat::Blocks {
+            size,
+            suffix: suffix.to_string(),
+        }
+    }
+
+    #[test]
+    fn block_size_units() {
+        assert_eq!(parse_block_size("K"), Ok(blocks(1024, "K")));
+        assert_eq!(parse_block_size("k"), Ok(blocks(1024, "k")));
+        assert_eq!(parse_block_size("KB"), Ok(blocks(1000, "KB")));
+        assert_eq!(parse_block_size("KiB"), Ok(blocks(1024, "KiB")));
+        assert_eq!(parse_block_size("MB"), Ok(blocks(1000 * 1000, "MB")));
+        assert_eq!(parse_block_size("G"), Ok(blocks(1 << 30, "G")));
+        assert_eq!(parse_block_size("human-readable"), Ok(SizeFormat::Human));
+        assert_eq!(parse_block_size("si"), Ok(SizeFormat::Si));
+    }
+
+    #[test]
+    fn block_size_number_hides_suffix() {
+        assert_eq!(parse_block_size("1024"), Ok(SizeFormat::blocks(1024)));
+        assert_eq!(parse_block_size("4K"), Ok(SizeFormat::blocks(4096)));
+        assert_eq!(parse_block_size("2KB"), Ok(SizeFormat::blocks(2000)));
+    }
+
+    #[test]
+    fn block_size_rejected() {
+        assert!(parse_block_size("0").is_err());
+        assert!(parse_block_size("0K").is_err());
+        assert!(parse_block_size("X").is_err());
+        assert!(parse_block_size("Kb").is_err());
+        assert!(parse_block_size("K1").is_err());
+        assert!(parse_block_size("99999999999999999999").is_err());
+        assert!(parse_block_size("16E").is_err());
+        assert!(parse_block_size("8E").is_ok());
+    }
+
+    #[test]
+    fn blocks_round_up() {
+        assert_eq!(format_size(0, &SizeFormat::blocks(1024)), "0");
+        assert_eq!(format_size(1, &SizeFormat::blocks(1024)), "1");
+        assert_eq!(format_size(1025, &SizeFormat::blocks(1024)), "2");
+        assert_eq!(format_size(2048, &blocks(1024, "K")), "2K");
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human(0, 1024, false), "0");
+        assert_eq!(human(1023, 1024, false), "1023");
+        assert_eq!(human(1024, 1024, false), "1.0K");
+        assert_eq!(human(1025, 1024, false), "1.1K");
+        assert_eq!(human(9 * 1024 + 1, 1024, false), "9.1K");
+        assert_eq!(human(1 << 20, 1024, false), "1.0M");
+        assert_eq!(human(u64::MAX, 1024, false), "16E");
+    }
+
+    #[test]
+    fn human_ten_boundary() {
+        /* Just below ten rounds up to ten, which has no decimal */
+        assert_eq!(human(10 * 1024 - 1, 1024, false), "10K");
+        assert_eq!(human(10 * 1024, 1024, false), "10K");
+        assert_eq!(human(10 * 1024 + 1, 1024, false), "11K");
+    }
+
+    #[test]
+    fn human_carries_to_next_unit() {
+        /* 1023.5K rounds up to 1024K, which is shown as 1.0M */
+        assert_eq!(human(1023 * 1024 + 512, 1024, false), "1.0M");
+        assert_eq!(human(1023 * 1024, 1024, false), "1023K");
+    }
+
+    #[test]
+    fn si_sizes() {
+        assert_eq!(human(999, 1000, true), "999");
+        assert_eq!(human(1000, 1000, true), "1.0k");
+        assert_eq!(human(1_500_000, 1000, true), "1.5M");
+        assert_eq!(human(999_500, 1000, true), "1.0M");
+    }
+}
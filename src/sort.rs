@@ -0,0 +1,203 @@
+use clap::ValueEnum;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+
+use crate::time::{self, TimeKind};
+use crate::{FSFile, FSFileType};
+
+/// Order of the entries in a directory
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+pub enum SortKey {
+    /// Alphabetically by name
+    Name,
+    /// Largest first
+    Size,
+    /// Newest first
+    Time,
+    /// Alphabetically by extension
+    Extension,
+    /// Natural order of the numbers in the names
+    Version,
+    /// Directory order
+    None,
+}
+
+/// Sort the entries of a single directory
+pub fn sort_files(
+    files: &mut [FSFile],
+    key: SortKey,
+    time: TimeKind,
+    reverse: bool,
+    dirs_first: bool,
+) {
+    if key != SortKey::None {
+        files.sort_by(|a, b| {
+            let ordering = compare(a, b, key, time);
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    if dirs_first {
+        /* Stable sort, so the order within the groups is kept */
+        files.sort_by_key(|f| !is_dir(f));
+    }
+}
+
+fn compare(a: &FSFile, b: &FSFile, key: SortKey, time: TimeKind) -> Ordering {
+    let by_name = || a.name.cmp(&b.name);
+    match key {
+        SortKey::Name | SortKey::None => by_name(),
+        /* Entries that could not be accessed go last, like the smallest and oldest */
+        SortKey::Size => size(b).cmp(&size(a)).then_with(by_name),
+        SortKey::Time => timestamp(b, time)
+            .cmp(&timestamp(a, time))
+            .then_with(by_name),
+        SortKey::Extension => extension(&a.name)
+            .cmp(extension(&b.name))
+            .then_with(by_name),
+        SortKey::Version => version_cmp(&a.name, &b.name).then_with(by_name),
+    }
+}
+
+fn size(fs_file: &FSFile) -> Option<u64> {
+    fs_file.metadata.as_ref().map(|m| m.size())
+}
+
+fn timestamp(fs_file: &FSFile, time: TimeKind) -> Option<(i64, i64)> {
+    time::timestamp(fs_file.metadata.as_ref()?, time)
+}
+
+/// Directories and links to directories are grouped together
+fn is_dir(fs_file: &FSFile) -> bool {
+    match &fs_file.entry_type {
+        FSFileType::Dir(_) => true,
+        FSFileType::Symlink(link) => link.target_metadata.as_ref().is_some_and(|m| m.is_dir()),
+        _ => false,
+    }
+}
+
+/// Text after the last dot, empty for names without one and for dotfiles
+fn extension(name: &OsStr) -> &[u8] {
+    let name = name.as_bytes();
+    match name.iter().rposition(|c| *c == b'.') {
+        Some(0) | None => &[],
+        Some(i) => &name[i + 1..],
+    }
+}
+
+/// Compare names where runs of digits are compared by their numeric value
+fn version_cmp(a: &OsStr, b: &OsStr) -> Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+
+    while !a.is_empty() && !b.is_empty() {
+        let a_digits = a.iter().take_while(|c| c.is_ascii_digit()).count();
+        let b_digits = b.iter().take_while(|c| c.is_ascii_digit()).count();
+
+        let ordering = if a_digits > 0 && b_digits > 0 {
+            /* Leading zeros do not change the value of a number */
+            let a_num = trim_zeros(&a[..a_digits]);
+            let b_num = trim_zeros(&b[..b_digits]);
+            let ordering = a_num.len().cmp(&b_num.len()).then(a_num.cmp(b_num));
+            a = &a[a_digits..];
+            b = &b[b_digits..];
+            ordering
+        } else {
+            let ordering = a[0].cmp(&b[0]);
+            a = &a[1..];
+            b = &b[1..];
+            ordering
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|c| **c == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    fn version(a: &str, b: &str) -> Ordering {
+        version_cmp(OsStr::new(a), OsStr::new(b))
+    }
+
+    fn sorted(names: &[&str], key: SortKey) -> Vec<String> {
+        let mut files: Vec<FSFile> = names
+            .iter()
+            .map(|name| FSFile {
+                name: OsString::from(name),
+                path_buf: PathBuf::from(name),
+                metadata: None,
+                entry_type: FSFileType::File,
+            })
+            .collect();
+        sort_files(&mut files, key, TimeKind::Mtime, false, false);
+        files
+            .iter()
+            .map(|f| f.name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn numbers_by_value() {
+        assert_eq!(version("file9", "file10"), Ordering::Less);
+        assert_eq!(version("file10", "file9"), Ordering::Greater);
+        assert_eq!(version("a2b", "a10b"), Ordering::Less);
+        assert_eq!(
+            sorted(&["file10", "file9", "file1"], SortKey::Version),
+            ["file1", "file9", "file10"]
+        );
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(version("file01", "file1"), Ordering::Equal);
+        assert_eq!(version("file001", "file2"), Ordering::Less);
+        /* Equal numbers fall back on the name */
+        assert_eq!(
+            sorted(&["file1", "file01"], SortKey::Version),
+            ["file01", "file1"]
+        );
+    }
+
+    #[test]
+    fn differ_after_digits() {
+        assert_eq!(version("v1a", "v1b"), Ordering::Less);
+        assert_eq!(version("v10.tar", "v10.gz"), Ordering::Greater);
+        assert_eq!(version("v2", "v2.1"), Ordering::Less);
+        assert_eq!(version("1", "1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(extension(OsStr::new("main.rs")), b"rs");
+        assert_eq!(extension(OsStr::new("archive.tar.gz")), b"gz");
+        assert_eq!(extension(OsStr::new("Makefile")), b"");
+        assert_eq!(extension(OsStr::new(".bashrc")), b"");
+        assert_eq!(extension(OsStr::new(".config.toml")), b"toml");
+        assert_eq!(extension(OsStr::new("trailing.")), b"");
+        assert_eq!(
+            sorted(
+                &["b.rs", ".hidden", "a.txt", "c", "a.rs"],
+                SortKey::Extension
+            ),
+            [".hidden", "c", "a.rs", "b.rs", "a.txt"]
+        );
+    }
+}
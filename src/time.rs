@@ -0,0 +1,262 @@
+use clap::ValueEnum;
+use std::ffi::CString;
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Timestamps older than this, or in the future, also show the year
+static SIX_MONTHS: i64 = 31_556_952 / 2;
+
+/// Which timestamp of a file to show and sort by
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+pub enum TimeKind {
+    /// Last modification
+    #[value(alias = "modification")]
+    Mtime,
+    /// Last access
+    #[value(alias = "access", alias = "use")]
+    Atime,
+    /// Last status change
+    #[value(alias = "status")]
+    Ctime,
+    /// Creation of the file
+    #[value(alias = "creation")]
+    Birth,
+}
+
+impl TimeKind {
+    /// Title of the time column
+    pub fn header(&self) -> &'static str {
+        match self {
+            TimeKind::Mtime => "Modified",
+            TimeKind::Atime => "Accessed",
+            TimeKind::Ctime => "Changed",
+            TimeKind::Birth => "Created",
+        }
+    }
+}
+
+/// How timestamps are formatted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// `Jan  2 15:04` for recent files and `Jan  2  2006` for older ones
+    Default,
+    /// `01-02 15:04` for recent files and `2006-01-02` for older ones
+    Iso,
+    /// `2006-01-02 15:04`
+    LongIso,
+    /// `2006-01-02 15:04:05.000000000 -0700`
+    FullIso,
+    /// `3 hours ago`
+    Relative,
+    /// strftime formats for recent and older files, from `+FORMAT`
+    Format { recent: String, old: String },
+}
+
+/// Parse the argument of `--time-style`
+pub fn parse_time_style(arg: &str) -> Result<TimeStyle, String> {
+    if let Some(format) = arg.strip_prefix('+') {
+        /* A newline separates the formats for recent and older files */
+        let (recent, old) = format.split_once('\n').unwrap_or((format, format));
+        return Ok(TimeStyle::Format {
+            recent: recent.to_string(),
+            old: old.to_string(),
+        });
+    }
+
+    match arg {
+        "default" | "locale" => Ok(TimeStyle::Default),
+        "iso" => Ok(TimeStyle::Iso),
+        "long-iso" => Ok(TimeStyle::LongIso),
+        "full-iso" => Ok(TimeStyle::FullIso),
+        "relative" => Ok(TimeStyle::Relative),
+        _ => Err(format!("invalid time style: {}", arg)),
+    }
+}
+
+/// Seconds and nanoseconds since the epoch, None when the timestamp is not available
+pub fn timestamp(metadata: &Metadata, kind: TimeKind) -> Option<(i64, i64)> {
+    match kind {
+        TimeKind::Mtime => Some((metadata.mtime(), metadata.mtime_nsec())),
+        TimeKind::Atime => Some((metadata.atime(), metadata.atime_nsec())),
+        TimeKind::Ctime => Some((metadata.ctime(), metadata.ctime_nsec())),
+        /* The birth time is read through statx by the standard library */
+        TimeKind::Birth => {
+            let created = metadata.created().ok()?;
+            let since_epoch = created.duration_since(UNIX_EPOCH).ok()?;
+            Some((
+                since_epoch.as_secs() as i64,
+                since_epoch.subsec_nanos() as i64,
+            ))
+        }
+    }
+}
+
+/// Format the timestamp, `?` when it is not available
+pub fn format_time(time: Option<(i64, i64)>, style: &TimeStyle) -> String {
+    let Some((secs, nsec)) = time else {
+        return String::from("?");
+    };
+    let now = now();
+    let recent = secs > now - SIX_MONTHS && secs <= now;
+
+    match style {
+        TimeStyle::Default if recent => strftime("%b %e %H:%M", secs, nsec),
+        TimeStyle::Default => strftime("%b %e  %Y", secs, nsec),
+        TimeStyle::Iso if recent => strftime("%m-%d %H:%M", secs, nsec),
+        TimeStyle::Iso => strftime("%Y-%m-%d ", secs, nsec),
+        TimeStyle::LongIso => strftime("%Y-%m-%d %H:%M", secs, nsec),
+        TimeStyle::FullIso => strftime("%Y-%m-%d %H:%M:%S.%N %z", secs, nsec),
+        TimeStyle::Relative => relative(now - secs),
+        TimeStyle::Format { recent: format, .. } if recent => strftime(format, secs, nsec),
+        TimeStyle::Format { old: format, .. } => strftime(format, secs, nsec),
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Describe an age in seconds with its largest unit, e.g. `5 minutes ago`
+fn relative(age: i64) -> String {
+    static UNITS: [(i64, &str); 6] = [
+        (365 * 24 * 3600, "year"),
+        (30 * 24 * 3600, "month"),
+        (24 * 3600, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+        (1, "second"),
+    ];
+
+    if age == 0 {
+        return String::from("now");
+    }
+
+    let seconds = age.abs();
+    let (size, unit) = UNITS
+        .iter()
+        .find(|(size, _)| seconds >= *size)
+        .copied()
+        .unwrap_or(UNITS[UNITS.len() - 1]);
+    let count = seconds / size;
+    let plural = if count == 1 { "" } else { "s" };
+
+    if age > 0 {
+        format!("{} {}{} ago", count, unit, plural)
+    } else {
+        format!("in {} {}{}", count, unit, plural)
+    }
+}
+
+/// Format the time in the local time zone with strftime, adding `%N` for nanoseconds
+fn strftime(format: &str, secs: i64, nsec: i64) -> String {
+    let mut expanded = String::with_capacity(format.len() + 8);
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('%', Some('N')) => {
+                chars.next();
+                expanded.push_str(&format!("{:09}", nsec));
+            }
+            ('%', Some('%')) => {
+                chars.next();
+                expanded.push_str("%%");
+            }
+            _ => expanded.push(c),
+        }
+    }
+
+    let Ok(c_format) = CString::new(expanded) else {
+        return secs.to_string();
+    };
+
+    let time = secs as libc::time_t;
+    // SAFETY: tm is plain old data that localtime_r fills in
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers are valid for the duration of the call
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return secs.to_string();
+    }
+
+    let mut buf = vec![0u8; 256];
+    // SAFETY: strftime writes at most buf.len() bytes including the terminating nul
+    let len = unsafe {
+        libc::strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            c_format.as_ptr(),
+            &tm,
+        )
+    };
+    buf.truncate(len);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_styles() {
+        assert_eq!(parse_time_style("iso"), Ok(TimeStyle::Iso));
+        assert_eq!(parse_time_style("long-iso"), Ok(TimeStyle::LongIso));
+        assert_eq!(parse_time_style("full-iso"), Ok(TimeStyle::FullIso));
+        assert_eq!(parse_time_style("relative"), Ok(TimeStyle::Relative));
+        assert_eq!(parse_time_style("locale"), Ok(TimeStyle::Default));
+    }
+
+    #[test]
+    fn format_styles() {
+        assert_eq!(
+            parse_time_style("+%H:%M"),
+            Ok(TimeStyle::Format {
+                recent: String::from("%H:%M"),
+                old: String::from("%H:%M"),
+            })
+        );
+        /* Recent files use the first format and older ones the second */
+        assert_eq!(
+            parse_time_style("+%b %e %H:%M\n%Y-%m-%d"),
+            Ok(TimeStyle::Format {
+                recent: String::from("%b %e %H:%M"),
+                old: String::from("%Y-%m-%d"),
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_style() {
+        assert!(parse_time_style("fancy").is_err());
+        assert!(parse_time_style("").is_err());
+        assert!(parse_time_style("ISO").is_err());
+    }
+
+    #[test]
+    fn relative_past() {
+        assert_eq!(relative(0), "now");
+        assert_eq!(relative(1), "1 second ago");
+        assert_eq!(relative(59), "59 seconds ago");
+        assert_eq!(relative(60), "1 minute ago");
+        assert_eq!(relative(2 * 3600 + 59), "2 hours ago");
+        assert_eq!(relative(24 * 3600), "1 day ago");
+        assert_eq!(relative(45 * 24 * 3600), "1 month ago");
+        assert_eq!(relative(3 * 365 * 24 * 3600), "3 years ago");
+    }
+
+    #[test]
+    fn relative_future() {
+        assert_eq!(relative(-1), "in 1 second");
+        assert_eq!(relative(-120), "in 2 minutes");
+        assert_eq!(relative(-24 * 3600), "in 1 day");
+    }
+
+    #[test]
+    fn nanoseconds() {
+        assert_eq!(strftime("%N", 0, 5), "000000005");
+        assert_eq!(strftime("%%N", 0, 5), "%N");
+        assert_eq!(format_time(None, &TimeStyle::Iso), "?");
+    }
+}